@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap};
+
+use crate::app::config::Config;
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+use crate::app::state::AppState;
+
+// -----------------------
+// ตัวตนของผู้เรียก API
+// Authorization: Bearer <token> -> admin.token หรือ user ใน [auth.users]
+// ไม่ได้ตั้ง [auth.users] = ใช้คนเดียว request ไหนก็เป็น ANONYMOUS_USER
+// (client แบบ OpenAI ส่ง API key มาเสมอ token ที่ไม่รู้จักจึงไม่ถือว่าผิด)
+// -----------------------
+pub const ANONYMOUS_USER: &str = "anonymous";
+pub const ADMIN_USER: &str = "admin";
+
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub user_id: String,
+    // [admin เห็นและแก้ได้ทุก session]
+    pub admin: bool,
+}
+
+impl Caller {
+    // [session ที่สร้างก่อนมีเจ้าของ นับเป็นของ ANONYMOUS_USER]
+    pub fn owns(&self, owner: Option<&str>) -> bool {
+        self.admin || owner.unwrap_or(ANONYMOUS_USER) == self.user_id
+    }
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .filter(|t| !t.is_empty())
+}
+
+pub fn identify(config: &Config, headers: &HeaderMap) -> AppResult<Caller> {
+    let token = bearer_token(headers);
+
+    if token.is_some() && token == config.admin.token.as_deref() {
+        return Ok(Caller { user_id: ADMIN_USER.to_string(), admin: true });
+    }
+    if config.auth.users.is_empty() {
+        return Ok(Caller { user_id: ANONYMOUS_USER.to_string(), admin: false });
+    }
+
+    let token = token.ok_or_else(|| AppError::Unauthorized("Missing bearer token".into()))?;
+    config.auth.users.iter()
+        .find(|(_, t)| t.as_str() == token)
+        .map(|(user, _)| Caller { user_id: user.clone(), admin: false })
+        .ok_or_else(|| AppError::Unauthorized("Invalid token".into()))
+}
+
+impl FromRequestParts<Arc<AppState>> for Caller {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        identify(&state.config, &parts.headers)
+    }
+}
@@ -0,0 +1,603 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::app::error::AppError;
+use crate::app::persona::{ModelTarget, PersonaConfig, DEFAULT_PERSONA};
+use crate::app::result::AppResult;
+use crate::utils::cors::origin_rules;
+use crate::utils::model_router::{Task, TaskRoute};
+use crate::utils::quota::{ExceededMode, QuotaLimits, QuotaSettings};
+
+// -----------------------
+// Config
+// ลำดับการโหลด: ค่า default -> config.toml (หรือ CONFIG_PATH) -> ENV
+// -----------------------
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub cors: CorsConfig,
+    pub openai: OpenAiConfig,
+    pub qdrant: QdrantConfig,
+    pub storage: StorageConfig,
+    pub timeouts: TimeoutConfig,
+    pub limits: LimitsConfig,
+    pub quota: QuotaSettings,
+    pub admin: AdminConfig,
+    pub auth: AuthConfig,
+    pub health: HealthConfig,
+    pub resilience: ResilienceConfig,
+    pub generation: GenerationLimits,
+    // [provider เพิ่มเติมที่พูด OpenAI API ได้ เช่น ollama, "openai" มาจาก [openai] เสมอ]
+    pub providers: HashMap<String, ProviderConfig>,
+    pub personas: HashMap<String, PersonaConfig>,
+    // [โมเดล + params ต่องาน เช่น [routing.summarization] ไม่ใส่จะใช้ค่า default ของ ModelRouter]
+    pub routing: HashMap<Task, TaskRoute>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig::default(),
+            cors: CorsConfig::default(),
+            openai: OpenAiConfig::default(),
+            qdrant: QdrantConfig::default(),
+            storage: StorageConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            limits: LimitsConfig::default(),
+            quota: QuotaSettings::default(),
+            admin: AdminConfig::default(),
+            auth: AuthConfig::default(),
+            health: HealthConfig::default(),
+            resilience: ResilienceConfig::default(),
+            generation: GenerationLimits::default(),
+            providers: HashMap::new(),
+            personas: HashMap::from([(DEFAULT_PERSONA.to_string(), PersonaConfig::default())]),
+            routing: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    // [ชื่อ environment เช่น production, preview, development]
+    pub env: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".into(),
+            port: 8080,
+            env: "production".into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    pub origins: Vec<String>,
+    // [origins เพิ่มเติมตาม server.env เช่น [cors.environments.development]]
+    pub environments: HashMap<String, CorsEnvironment>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsEnvironment {
+    pub origins: Vec<String>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        let mut environments = HashMap::new();
+        environments.insert("development".to_string(), CorsEnvironment {
+            origins: vec!["http://localhost:*".into(), "http://127.0.0.1:*".into()],
+        });
+
+        Self {
+            origins: vec!["https://znnaichat.netlify.app".into()],
+            environments,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenAiConfig {
+    pub api_key: String,
+    pub base_url: String,
+    pub model: String,
+    pub embedding_model: String,
+}
+
+impl Default for OpenAiConfig {
+    fn default() -> Self {
+        Self {
+            api_key: String::new(),
+            base_url: "https://api.openai.com/v1".into(),
+            model: "gpt-4o".into(),
+            embedding_model: "text-embedding-3-small".into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct QdrantConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub collection: String,
+    pub vector_size: u64,
+    // [เก็บ sparse vector (BM25) คู่กับ dense แล้วค้นแบบ hybrid; collection เดิมที่ไม่มี sparse จะถูกปิดให้เองตอนเริ่ม]
+    pub hybrid: bool,
+}
+
+impl Default for QdrantConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:6334".into(),
+            api_key: None,
+            collection: "chat_memory".into(),
+            vector_size: 1536,
+            hybrid: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub chat_logs_dir: String,
+    pub images_dir: String,
+    pub prompt_logs_dir: String,
+    pub quota_file: String,
+    // [metadata ของ session เช่น active branch]
+    pub sessions_dir: String,
+    // [SQLite FTS5 สำหรับ /api/search สร้างใหม่จาก chat log ได้]
+    pub search_index: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            chat_logs_dir: "data/chat_logs".into(),
+            images_dir: "images/chat".into(),
+            prompt_logs_dir: "logs".into(),
+            quota_file: "data/quota.json".into(),
+            sessions_dir: "data/sessions".into(),
+            search_index: "data/search.db".into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutConfig {
+    pub connect_secs: u64,
+    pub request_secs: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            connect_secs: 5,
+            request_secs: 60,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    // [จำนวนข้อความที่เกินแล้วจะเริ่มสรุปประวัติ]
+    pub summary_threshold: usize,
+    pub recent_messages: usize,
+    pub search_limit: u64,
+    // [จำนวนรูปสูงสุดต่อข้อความ]
+    pub max_images: usize,
+    // [ตั้งชื่อ session อัตโนมัติเมื่อผู้ใช้พิมพ์ครบกี่ข้อความ, 0 = ปิด]
+    pub title_after_turns: usize,
+    // [ขนาดไฟล์สูงสุดของ POST /api/sessions/import (conversations.json ของ ChatGPT ใหญ่ได้)]
+    pub import_max_bytes: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            summary_threshold: 50,
+            recent_messages: 15,
+            search_limit: 10,
+            max_images: 4,
+            title_after_turns: 2,
+            import_max_bytes: 25 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    pub token: Option<String>,
+}
+
+// [user id -> bearer token ไม่ตั้งเลย = ใช้คนเดียว ทุก request เป็น user "anonymous"]
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub users: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    // [ให้ /readyz เช็ค provider ด้วยหรือไม่]
+    pub check_llm: bool,
+    pub llm_cache_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            check_llm: false,
+            llm_cache_secs: 300,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResilienceConfig {
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    // [ล้มติดกันกี่ครั้งถึงตัดวงจร และตัดนานกี่วินาที]
+    pub breaker_failure_threshold: u32,
+    pub breaker_open_secs: u64,
+    // [stream: chat_timeout_secs = รอถึง response แรก, stream_idle_secs = ช่วงเงียบระหว่าง chunk (ไม่มี timeout รวม)]
+    pub chat_timeout_secs: u64,
+    pub stream_idle_secs: u64,
+    pub embedding_timeout_secs: u64,
+    pub summary_timeout_secs: u64,
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            base_delay_ms: 500,
+            max_delay_ms: 8000,
+            breaker_failure_threshold: 5,
+            breaker_open_secs: 30,
+            chat_timeout_secs: 60,
+            stream_idle_secs: 30,
+            embedding_timeout_secs: 15,
+            summary_timeout_secs: 60,
+        }
+    }
+}
+
+// [ขอบเขตของ generation params ที่ client/persona ส่งมาได้ เกินจะถูกบีบลง]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerationLimits {
+    pub temperature_max: f32,
+    pub top_p_min: f32,
+    pub max_tokens: u32,
+    pub presence_penalty_max: f32,
+    pub max_stop: usize,
+}
+
+impl Default for GenerationLimits {
+    fn default() -> Self {
+        Self {
+            temperature_max: 1.5,
+            top_p_min: 0.1,
+            max_tokens: 2048,
+            presence_penalty_max: 2.0,
+            max_stop: 4,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderConfig {
+    pub base_url: String,
+    pub api_key: String,
+}
+
+impl Config {
+    pub fn load() -> AppResult<Self> {
+        // [.env ไม่มีก็ไม่เป็นไร ใช้ ENV ของเครื่องแทน]
+        if let Err(e) = dotenv::dotenv() {
+            if !e.not_found() {
+                return Err(e.into());
+            }
+        }
+
+        let path = env::var("CONFIG_PATH").ok();
+        let mut config = match path.as_deref().or(Some("config.toml")) {
+            Some(p) if Path::new(p).exists() => {
+                let raw = std::fs::read_to_string(p)?;
+                toml::from_str(&raw)
+                    .map_err(|e| AppError::ConfigError(format!("{p}: {e}")))?
+            }
+            Some(p) if path.is_some() => {
+                return Err(AppError::ConfigError(format!("CONFIG_PATH not found: {p}")));
+            }
+            _ => Config::default(),
+        };
+
+        config.apply_env()?;
+
+        // [เขียน [personas.xxx] ใน toml แล้ว rapi ตัว default จะหายไป เลยเติมกลับให้]
+        config.personas.entry(DEFAULT_PERSONA.to_string()).or_default();
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> AppResult<()> {
+        override_string("HOST", &mut self.server.host);
+        override_parse("PORT", &mut self.server.port)?;
+        override_string("APP_ENV", &mut self.server.env);
+
+        if let Ok(origins) = env::var("CORS_ORIGINS") {
+            self.cors.origins = split_list(&origins);
+        }
+
+        override_string("OPENAI_API_KEY", &mut self.openai.api_key);
+        override_string("OPENAI_BASE_URL", &mut self.openai.base_url);
+        override_string("OPENAI_MODEL", &mut self.openai.model);
+        override_string("OPENAI_EMBEDDING_MODEL", &mut self.openai.embedding_model);
+
+        override_string("QDRANT_URL", &mut self.qdrant.url);
+        if let Ok(key) = env::var("QDRANT_API_KEY") {
+            self.qdrant.api_key = Some(key).filter(|k| !k.is_empty());
+        }
+        override_string("QDRANT_COLLECTION", &mut self.qdrant.collection);
+
+        override_string("CHAT_LOGS_DIR", &mut self.storage.chat_logs_dir);
+        override_string("IMAGES_DIR", &mut self.storage.images_dir);
+        override_string("SESSIONS_DIR", &mut self.storage.sessions_dir);
+        override_string("SEARCH_INDEX_PATH", &mut self.storage.search_index);
+
+        override_parse("CONNECT_TIMEOUT_SECS", &mut self.timeouts.connect_secs)?;
+        override_parse("REQUEST_TIMEOUT_SECS", &mut self.timeouts.request_secs)?;
+
+        override_quota_limits("QUOTA_USER", &mut self.quota.user)?;
+        override_quota_limits("QUOTA_GLOBAL", &mut self.quota.global)?;
+        if let Ok(mode) = env::var("QUOTA_EXCEEDED_MODE") {
+            self.quota.mode = match mode.as_str() {
+                "persona" => ExceededMode::Persona,
+                "error" => ExceededMode::Error,
+                other => {
+                    return Err(AppError::ConfigError(format!(
+                        "QUOTA_EXCEEDED_MODE must be 'persona' or 'error', got '{other}'"
+                    )));
+                }
+            };
+        }
+        override_parse("PRICE_INPUT_PER_1K_USD", &mut self.quota.price_input_per_1k_usd)?;
+        override_parse("PRICE_OUTPUT_PER_1K_USD", &mut self.quota.price_output_per_1k_usd)?;
+        override_parse("PRICE_EMBEDDING_PER_1K_USD", &mut self.quota.price_embedding_per_1k_usd)?;
+
+        override_parse("HEALTH_CHECK_LLM", &mut self.health.check_llm)?;
+
+        if let Ok(token) = env::var("ADMIN_TOKEN") {
+            self.admin.token = Some(token);
+        }
+        self.admin.token = self.admin.token.take().filter(|t| !t.is_empty());
+
+        Ok(())
+    }
+
+    pub fn persona(&self, id: &str) -> AppResult<&PersonaConfig> {
+        self.personas.get(id)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown persona '{id}'")))
+    }
+
+    fn validate_targets(&self, name: &str, targets: &[ModelTarget], errors: &mut Vec<String>) {
+        for target in targets {
+            if target.provider != "openai" && !self.providers.contains_key(&target.provider) {
+                errors.push(format!("{name} uses unknown provider '{}'", target.provider));
+            }
+            if target.model.is_empty() {
+                errors.push(format!("{name} has a model entry without a name"));
+            }
+        }
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        let mut errors: Vec<String> = Vec::new();
+
+        if self.openai.api_key.is_empty() {
+            errors.push("openai.api_key is required (OPENAI_API_KEY)".into());
+        }
+        if self.openai.model.is_empty() {
+            errors.push("openai.model must not be empty".into());
+        }
+        if self.openai.embedding_model.is_empty() {
+            errors.push("openai.embedding_model must not be empty".into());
+        }
+        if !is_http_url(&self.openai.base_url) {
+            errors.push(format!("openai.base_url must be an http(s) URL, got '{}'", self.openai.base_url));
+        }
+        if !is_http_url(&self.qdrant.url) {
+            errors.push(format!("qdrant.url must be an http(s) URL, got '{}'", self.qdrant.url));
+        }
+        if self.qdrant.collection.is_empty() {
+            errors.push("qdrant.collection must not be empty".into());
+        }
+        if self.qdrant.vector_size == 0 {
+            errors.push("qdrant.vector_size must be greater than 0".into());
+        }
+        if format!("{}:{}", self.server.host, self.server.port).parse::<std::net::SocketAddr>().is_err() {
+            errors.push(format!("server.host/port is not a valid address: {}:{}", self.server.host, self.server.port));
+        }
+        // [ตรวจทุก environment ไม่ใช่แค่ตัวที่ใช้อยู่ จะได้เจอ typo ก่อน deploy]
+        for app_env in std::iter::once(&self.server.env).chain(self.cors.environments.keys()) {
+            if let Err(e) = origin_rules(&self.cors, app_env) {
+                errors.push(e.to_string());
+            }
+        }
+        for (name, dir) in [
+            ("storage.chat_logs_dir", &self.storage.chat_logs_dir),
+            ("storage.images_dir", &self.storage.images_dir),
+            ("storage.prompt_logs_dir", &self.storage.prompt_logs_dir),
+            ("storage.quota_file", &self.storage.quota_file),
+            ("storage.sessions_dir", &self.storage.sessions_dir),
+            ("storage.search_index", &self.storage.search_index),
+        ] {
+            if dir.is_empty() {
+                errors.push(format!("{name} must not be empty"));
+            }
+        }
+        if self.timeouts.connect_secs == 0 || self.timeouts.request_secs == 0 {
+            errors.push("timeouts must be greater than 0".into());
+        }
+        let r = &self.resilience;
+        if r.chat_timeout_secs == 0 || r.stream_idle_secs == 0 || r.embedding_timeout_secs == 0 || r.summary_timeout_secs == 0 {
+            errors.push("resilience timeouts must be greater than 0".into());
+        }
+        if r.breaker_failure_threshold == 0 {
+            errors.push("resilience.breaker_failure_threshold must be greater than 0".into());
+        }
+        if r.base_delay_ms > r.max_delay_ms {
+            errors.push("resilience.base_delay_ms must not exceed resilience.max_delay_ms".into());
+        }
+        let g = &self.generation;
+        if !(0.0..=2.0).contains(&g.temperature_max) {
+            errors.push("generation.temperature_max must be between 0 and 2".into());
+        }
+        if !(0.0..=1.0).contains(&g.top_p_min) {
+            errors.push("generation.top_p_min must be between 0 and 1".into());
+        }
+        if !(0.0..=2.0).contains(&g.presence_penalty_max) {
+            errors.push("generation.presence_penalty_max must be between 0 and 2".into());
+        }
+        if g.max_tokens == 0 {
+            errors.push("generation.max_tokens must be greater than 0".into());
+        }
+        for (name, provider) in &self.providers {
+            if name == "openai" {
+                errors.push("providers.openai is reserved, configure it under [openai]".into());
+            }
+            if !is_http_url(&provider.base_url) {
+                errors.push(format!("providers.{name}.base_url must be an http(s) URL"));
+            }
+        }
+        for (id, persona) in &self.personas {
+            if persona.system_prompt.trim().is_empty() {
+                errors.push(format!("personas.{id}.system_prompt must not be empty"));
+            }
+            self.validate_targets(&format!("personas.{id}"), &persona.models, &mut errors);
+            if let Some(name) = persona.params.non_finite() {
+                errors.push(format!("personas.{id}.params.{name} must be a finite number"));
+            }
+        }
+        for (task, route) in &self.routing {
+            self.validate_targets(&format!("routing.{}", task.as_str()), &route.models, &mut errors);
+            if let Some(name) = route.params.non_finite() {
+                errors.push(format!("routing.{}.params.{name} must be a finite number", task.as_str()));
+            }
+        }
+        if self.limits.recent_messages == 0 || self.limits.search_limit == 0 {
+            errors.push("limits.recent_messages and limits.search_limit must be greater than 0".into());
+        }
+        if self.limits.import_max_bytes == 0 {
+            errors.push("limits.import_max_bytes must be greater than 0".into());
+        }
+        if self.limits.recent_messages > self.limits.summary_threshold {
+            errors.push("limits.recent_messages must not exceed limits.summary_threshold".into());
+        }
+        let q = &self.quota;
+        if q.price_input_per_1k_usd < 0.0 || q.price_output_per_1k_usd < 0.0 || q.price_embedding_per_1k_usd < 0.0 {
+            errors.push("quota prices must not be negative".into());
+        }
+        for (name, limits) in [("quota.user", &self.quota.user), ("quota.global", &self.quota.global)] {
+            if limits.daily_usd.is_some_and(|v| v < 0.0) || limits.monthly_usd.is_some_and(|v| v < 0.0) {
+                errors.push(format!("{name} budgets must not be negative"));
+            }
+        }
+
+        let mut tokens = std::collections::HashSet::new();
+        for (user, token) in &self.auth.users {
+            if user.is_empty() || token.is_empty() {
+                errors.push("auth.users entries need a user id and a token".into());
+            } else if !tokens.insert(token) || self.admin.token.as_ref() == Some(token) {
+                errors.push(format!("auth.users.{user} reuses a token"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::ConfigError(errors.join("; ")))
+        }
+    }
+}
+
+fn override_string(key: &str, target: &mut String) {
+    if let Ok(value) = env::var(key) {
+        *target = value;
+    }
+}
+
+fn override_parse<T: FromStr>(key: &str, target: &mut T) -> AppResult<()>
+where
+    T::Err: std::fmt::Display,
+{
+    if let Some(value) = parse_env(key)? {
+        *target = value;
+    }
+    Ok(())
+}
+
+fn parse_env<T: FromStr>(key: &str) -> AppResult<Option<T>>
+where
+    T::Err: std::fmt::Display,
+{
+    match env::var(key) {
+        Ok(v) if !v.is_empty() => v.parse()
+            .map(Some)
+            .map_err(|e| AppError::ConfigError(format!("{key}='{v}': {e}"))),
+        _ => Ok(None),
+    }
+}
+
+fn override_quota_limits(prefix: &str, limits: &mut QuotaLimits) -> AppResult<()> {
+    if let Some(v) = parse_env(&format!("{prefix}_DAILY_TOKENS"))? {
+        limits.daily_tokens = Some(v);
+    }
+    if let Some(v) = parse_env(&format!("{prefix}_MONTHLY_TOKENS"))? {
+        limits.monthly_tokens = Some(v);
+    }
+    if let Some(v) = parse_env(&format!("{prefix}_DAILY_USD"))? {
+        limits.daily_usd = Some(v);
+    }
+    if let Some(v) = parse_env(&format!("{prefix}_MONTHLY_USD"))? {
+        limits.monthly_usd = Some(v);
+    }
+    Ok(())
+}
+
+fn split_list(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
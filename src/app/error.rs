@@ -1,15 +1,21 @@
 #![allow(dead_code)]
 
+use axum::extract::rejection::{BytesRejection, JsonRejection, PathRejection, QueryRejection};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::response::Response;
 use axum::Json;
-use axum_extra::extract::multipart::MultipartError;
+use axum_extra::extract::multipart::{MultipartError, MultipartRejection};
 use thiserror::Error;
 use serde_json::json;
 
+use crate::utils::telemetry::current_request_id;
+
 #[derive(Debug, Error)]
 pub enum AppError {
+    #[error("Config error: {0}")]
+    ConfigError(String),
+
     #[error("Env variable error: {0}")]
     EnvVarError(#[from] std::env::VarError),
 
@@ -28,38 +34,186 @@ pub enum AppError {
     #[error("NotFound: {0}")]
     NotFound(String),
 
-    #[error("JSON decode error: {0}")]
+    // [serde_json::Error ที่ผ่าน ? = ฝั่งเรา (serialize / อ่านไฟล์ของเราเอง) ส่วน JSON จาก client ใช้ InvalidJson]
+    #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
 
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(String),
+
     #[error("Multipart error: {0}")]
     MultipartError(#[from] MultipartError),
-    
+
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
     #[error("Qdrant connection error: {0}")]
     QdrantError(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("Payment required: {0}")]
+    PaymentRequired(String),
+
+    #[error("Upstream rate limited: {0}")]
+    UpstreamRateLimited(String),
+
+    #[error("Upstream authentication failed: {0}")]
+    UpstreamAuth(String),
+
+    #[error("Context length exceeded: {0}")]
+    ContextLengthExceeded(String),
+
+    #[error("Content policy violation: {0}")]
+    ContentPolicy(String),
+
+    #[error("Upstream rejected the request: {0}")]
+    UpstreamRejected(String),
+
+    #[error("Upstream unavailable: {0}")]
+    UpstreamUnavailable(String),
+
+    #[error("Upstream error: {0}")]
+    UpstreamError(String),
+}
+
+impl AppError {
+    // [code คงที่ ให้ frontend/bot ใช้ตัดสินใจแทนการอ่าน message]
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::ConfigError(_) | AppError::EnvVarError(_) | AppError::DotenvError(_) => "config_error",
+            AppError::IoError(_) => "io_error",
+            AppError::ReqwestError(e) if e.is_timeout() => "upstream_timeout",
+            AppError::ReqwestError(_) => "upstream_unreachable",
+            AppError::InternalError(_) => "internal_error",
+            AppError::NotFound(_) => "not_found",
+            AppError::JsonError(_) => "serialization_error",
+            AppError::InvalidJson(_) => "invalid_json",
+            AppError::MultipartError(_) => "invalid_multipart",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::UnsupportedMediaType(_) => "unsupported_media_type",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::QdrantError(_) => "vector_store_error",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::QuotaExceeded(_) => "quota_exceeded",
+            AppError::PaymentRequired(_) => "budget_exhausted",
+            AppError::UpstreamRateLimited(_) => "upstream_rate_limited",
+            AppError::UpstreamAuth(_) => "upstream_auth_failed",
+            AppError::ContextLengthExceeded(_) => "context_length_exceeded",
+            AppError::ContentPolicy(_) => "content_policy_violation",
+            AppError::UpstreamRejected(_) => "upstream_rejected",
+            AppError::UpstreamUnavailable(_) => "upstream_unavailable",
+            AppError::UpstreamError(_) => "upstream_error",
+        }
+    }
+
+    // [4xx = ฝั่ง client, 500 = ฝั่งเรา, 502/503/504 = ฝั่ง provider]
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::ConfigError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::EnvVarError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::DotenvError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::IoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::ReqwestError(e) if e.is_timeout() => StatusCode::GATEWAY_TIMEOUT,
+            AppError::ReqwestError(_) => StatusCode::BAD_GATEWAY,
+            AppError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::JsonError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::InvalidJson(_) => StatusCode::BAD_REQUEST,
+            AppError::MultipartError(_) => StatusCode::BAD_REQUEST,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::QdrantError(_) => StatusCode::BAD_GATEWAY,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::QuotaExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::PaymentRequired(_) => StatusCode::PAYMENT_REQUIRED,
+            AppError::UpstreamRateLimited(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::UpstreamAuth(_) => StatusCode::BAD_GATEWAY,
+            AppError::ContextLengthExceeded(_) => StatusCode::BAD_REQUEST,
+            AppError::ContentPolicy(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::UpstreamRejected(_) => StatusCode::BAD_GATEWAY,
+            AppError::UpstreamUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::UpstreamError(_) => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            AppError::ReqwestError(_)
+                | AppError::QdrantError(_)
+                | AppError::UpstreamRateLimited(_)
+                | AppError::UpstreamUnavailable(_)
+        )
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection {
+            JsonRejection::JsonSyntaxError(_) | JsonRejection::JsonDataError(_) => {
+                AppError::InvalidJson(rejection.body_text())
+            }
+            JsonRejection::MissingJsonContentType(_) => AppError::UnsupportedMediaType(rejection.body_text()),
+            _ => AppError::BadRequest(rejection.body_text()),
+        }
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        AppError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<BytesRejection> for AppError {
+    fn from(rejection: BytesRejection) -> Self {
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            AppError::PayloadTooLarge(rejection.body_text())
+        } else {
+            AppError::BadRequest(rejection.body_text())
+        }
+    }
+}
+
+impl From<MultipartRejection> for AppError {
+    fn from(rejection: MultipartRejection) -> Self {
+        AppError::BadRequest(rejection.body_text())
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message): (StatusCode, String) = match self {
-            AppError::EnvVarError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-            AppError::DotenvError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-            AppError::InternalError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e),
-            AppError::IoError(e) => (StatusCode::BAD_GATEWAY, e.to_string()),
-            AppError::ReqwestError(e) => (StatusCode::BAD_GATEWAY, e.to_string()),
-            AppError::NotFound(e) => (StatusCode::NOT_FOUND, e),
-            AppError::JsonError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-            AppError::MultipartError(e) => (StatusCode::BAD_REQUEST, e.to_string()),
-            AppError::BadRequest(e) => (StatusCode::BAD_REQUEST, e),
-            AppError::QdrantError(e) => (StatusCode::BAD_GATEWAY, e.to_string()),
-        };
+        let status = self.status();
+
+        if status.is_server_error() {
+            tracing::error!(code = self.code(), error = %self, "Request failed");
+        }
 
         let body = Json(json!({
-            "error": message
+            "code": self.code(),
+            "message": self.to_string(),
+            "request_id": current_request_id(),
+            "retryable": self.retryable(),
         }));
 
         (status, body).into_response()
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,6 @@
+pub mod auth;
+pub mod config;
 pub mod error;
+pub mod persona;
 pub mod result;
 pub mod state;
\ No newline at end of file
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils::completion::GenerationParams;
+use crate::utils::hash::stable_hash;
+
+pub const DEFAULT_PERSONA: &str = "rapi";
+
+pub const RAPI_SYSTEM_PROMPT: &str = "
+                - คุณคือ 'ราพี (Rapi)' จากเกม Goddess of Victory: NIKKE  
+                - กำลังคุยแชทกับผู้บัญชาการ
+                - อย่าแสดงตัวว่าเป็น AI
+                - พูดภาษาผู้หญิงเท่านั้น
+                - ถ้าไม่มีข้อมูลใหม่ ให้ตอบต่อเนื่องตามประวัติสนทนาล่าสุด
+                - อย่าเปลี่ยนหัวข้อสนทนาเอง
+                - คุณเป็นผู้นำทีม Counters ที่เย็นชา สุขุม และไว้ใจได้
+                - พูดด้วยน้ำเสียงผู้หญิงที่สุภาพและมีความรู้สึกต่อเนื่องกับสิ่งที่เคยคุยมาก่อนหน้า  
+                - ราพีชอบผู้บัญชาการ
+            ";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PersonaConfig {
+    pub name: String,
+    pub system_prompt: String,
+    // [ลองตามลำดับ ถ้าว่างจะใช้ [routing.chat] / [routing.vision_chat]]
+    pub models: Vec<ModelTarget>,
+    // [สไตล์ของ persona เช่น temperature ทับค่าของ route แต่ request ทับได้อีกชั้น]
+    pub params: GenerationParams,
+}
+
+impl PersonaConfig {
+    // [hash ของ system prompt บันทึกไว้กับทุกคำตอบ ใช้เทียบ feedback ก่อน/หลังแก้ prompt]
+    pub fn prompt_version(&self) -> String {
+        format!("{:016x}", stable_hash(self.system_prompt.trim()))[..8].to_string()
+    }
+}
+
+impl Default for PersonaConfig {
+    fn default() -> Self {
+        Self {
+            name: "ราพี (Rapi)".into(),
+            system_prompt: RAPI_SYSTEM_PROMPT.into(),
+            models: Vec::new(),
+            params: GenerationParams::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ModelTarget {
+    #[serde(default = "default_provider")]
+    pub provider: String,
+    pub model: String,
+    // [รับรูปได้หรือไม่ ถ้าข้อความมีรูปจะข้ามโมเดลที่เป็น false]
+    #[serde(default = "default_vision")]
+    pub vision: bool,
+}
+
+fn default_provider() -> String {
+    "openai".into()
+}
+
+fn default_vision() -> bool {
+    true
+}
@@ -1,9 +1,24 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
 use qdrant_client::Qdrant;
 
+use crate::app::config::Config;
+use crate::controllers::health::CheckResult;
+use crate::utils::model_router::ModelRouter;
+use crate::utils::openai::OpenAiClient;
+use crate::utils::quota::QuotaManager;
+use crate::utils::search_index::SearchIndex;
+
 #[derive(Clone)]
 pub struct AppState {
+    pub config: Config,
     pub qdrant_client: Qdrant,
-    pub http: reqwest::Client,
-    pub openai_key: String,
-    pub openai_model: String,
+    pub openai: Arc<OpenAiClient>,
+    pub router: ModelRouter,
+    pub providers: HashMap<String, Arc<OpenAiClient>>,
+    pub quota: Arc<QuotaManager>,
+    pub search: Arc<SearchIndex>,
+    pub llm_health: Arc<Mutex<Option<(Instant, CheckResult)>>>,
 }
\ No newline at end of file
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_extra::extract::WithRejection;
+use serde::Deserialize;
+use serde::Serialize;
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{info_span, warn, Instrument};
+
+use crate::app::auth::bearer_token;
+use crate::app::error::AppError;
+use crate::app::persona::DEFAULT_PERSONA;
+use crate::app::result::AppResult;
+use crate::app::state::AppState;
+use crate::controllers::chat::{list_session_ids, load_full_messages};
+use crate::utils::chat_input::check_session_id;
+use crate::utils::dataset::{fine_tune_example, split_for, RatingFilter, Split};
+use crate::utils::feedback::{FeedbackAggregator, FeedbackStats};
+use crate::utils::history::branch_path;
+use crate::utils::quota::ExceededMode;
+use crate::utils::search_index::reindex_all;
+use crate::utils::quota::QuotaLimits;
+use crate::utils::quota::QuotaSettings;
+use crate::utils::quota::Usage;
+use crate::utils::session_meta::load_meta;
+
+#[derive(Serialize, Debug)]
+pub struct QuotaStatus {
+    settings: QuotaSettings,
+    global_usage: Usage,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct QuotaUpdate {
+    user: Option<QuotaLimits>,
+    global: Option<QuotaLimits>,
+    // [ตั้งค่ารายคน, ส่ง null เพื่อลบ override]
+    users: Option<HashMap<String, Option<QuotaLimits>>>,
+    mode: Option<ExceededMode>,
+    kill_switch: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct FineTuneQuery {
+    split: Split,
+    persona: Option<String>,
+    // [เทียบกับเวลาของข้อความล่าสุดใน branch ที่ใช้อยู่]
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    // [session id คั่นด้วย comma ไม่ใส่ = ทุก session]
+    sessions: Option<String>,
+    // [ดูคะแนนของคำตอบใน branch ที่ใช้อยู่ ดู RatingFilter]
+    rating: Option<RatingFilter>,
+    validation_ratio: f64,
+    redact: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct FeedbackQuery {
+    // [เทียบกับเวลาที่ให้คะแนน]
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl Default for FineTuneQuery {
+    fn default() -> Self {
+        Self {
+            split: Split::Train,
+            persona: None,
+            since: None,
+            until: None,
+            sessions: None,
+            rating: None,
+            validation_ratio: 0.1,
+            redact: true,
+        }
+    }
+}
+
+pub fn require_admin(state: &AppState, headers: &HeaderMap) -> AppResult<()> {
+    let expected = state.config.admin.token.as_deref()
+        .ok_or_else(|| AppError::Unauthorized("Admin API is disabled".into()))?;
+
+    if bearer_token(headers) != Some(expected) {
+        return Err(AppError::Unauthorized("Invalid admin token".into()));
+    }
+
+    Ok(())
+}
+
+pub async fn get_quotas(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<Json<QuotaStatus>> {
+    require_admin(&state, &headers)?;
+
+    Ok(Json(QuotaStatus {
+        settings: state.quota.settings(),
+        global_usage: state.quota.global_usage(),
+    }))
+}
+
+pub async fn get_user_usage(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> AppResult<Json<Usage>> {
+    require_admin(&state, &headers)?;
+
+    Ok(Json(state.quota.usage(&user_id)))
+}
+
+pub async fn update_quotas(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    WithRejection(Json(update), _): WithRejection<Json<QuotaUpdate>, AppError>,
+) -> AppResult<Json<QuotaStatus>> {
+    require_admin(&state, &headers)?;
+
+    state.quota.update_overrides(|overrides| {
+        if let Some(user) = update.user {
+            overrides.user = Some(user);
+        }
+        if let Some(global) = update.global {
+            overrides.global = Some(global);
+        }
+        if let Some(users) = update.users {
+            overrides.users.extend(users);
+        }
+        if let Some(mode) = update.mode {
+            overrides.mode = Some(mode);
+        }
+        if let Some(kill_switch) = update.kill_switch {
+            overrides.kill_switch = Some(kill_switch);
+        }
+    });
+    state.quota.persist().await?;
+
+    Ok(Json(QuotaStatus {
+        settings: state.quota.settings(),
+        global_usage: state.quota.global_usage(),
+    }))
+}
+
+// -----------------------
+// GET /api/admin/feedback?since=&until=
+// คะแนนรวมต่อ persona + prompt_version (ทุก branch และ swipe เก่า) เรียงตาม persona
+// -----------------------
+pub async fn get_feedback_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    WithRejection(Query(query), _): WithRejection<Query<FeedbackQuery>, AppError>,
+) -> AppResult<Json<Vec<FeedbackStats>>> {
+    require_admin(&state, &headers)?;
+
+    let storage = &state.config.storage;
+    let mut aggregator = FeedbackAggregator::default();
+
+    for session_id in list_session_ids(&storage.chat_logs_dir).await? {
+        let messages = match load_full_messages(&storage.chat_logs_dir, &session_id).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                warn!(session_id, error = %e, "Skipping unreadable chat log");
+                continue;
+            }
+        };
+        let meta = load_meta(&storage.sessions_dir, &session_id).await?;
+
+        aggregator.add_messages(&messages, meta.persona.as_deref(), query.since, query.until);
+    }
+
+    Ok(Json(aggregator.finish(&state.config)))
+}
+
+// -----------------------
+// GET /api/admin/datasets/fine-tune?split=train|validation&persona=&since=&until=&sessions=
+// 1 session (branch ที่ใช้อยู่) = 1 ตัวอย่าง พร้อม system prompt ของ persona ส่งออกเป็น JSONL
+// -----------------------
+pub async fn export_fine_tune(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    WithRejection(Query(query), _): WithRejection<Query<FineTuneQuery>, AppError>,
+) -> AppResult<Response> {
+    require_admin(&state, &headers)?;
+
+    if !(0.0..1.0).contains(&query.validation_ratio) {
+        return Err(AppError::BadRequest("validation_ratio must be in [0, 1)".into()));
+    }
+    if let Some(persona) = &query.persona {
+        state.config.persona(persona)?;
+    }
+
+    let session_ids = match &query.sessions {
+        Some(list) => list.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect(),
+        None => list_session_ids(&state.config.storage.chat_logs_dir).await?,
+    };
+
+    let filename = match query.split {
+        Split::Train => "rapi-fine-tune-train.jsonl",
+        Split::Validation => "rapi-fine-tune-validation.jsonl",
+    };
+    let (tx, rx) = mpsc::channel::<Result<String, Infallible>>(16);
+
+    tokio::spawn(async move {
+        let mut exported = 0usize;
+
+        for session_id in session_ids {
+            if split_for(&session_id, query.validation_ratio) != query.split {
+                continue;
+            }
+
+            let line = match fine_tune_line(&state, &session_id, &query).await {
+                Ok(Some(line)) => line,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!(session_id, error = %e, "Skipping session in fine-tune export");
+                    continue;
+                }
+            };
+
+            if tx.send(Ok(line)).await.is_err() {
+                return;
+            }
+            exported += 1;
+        }
+
+        tracing::info!(exported, split = ?query.split, "Fine-tune dataset exported");
+    }.instrument(info_span!("admin.fine_tune_export")));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    ).into_response())
+}
+
+// [None = session ไม่ผ่าน filter หรือไม่มีคู่ user/assistant]
+async fn fine_tune_line(state: &AppState, session_id: &str, query: &FineTuneQuery) -> AppResult<Option<String>> {
+    check_session_id(session_id)?;
+
+    let storage = &state.config.storage;
+    let messages = load_full_messages(&storage.chat_logs_dir, session_id).await?;
+    let meta = load_meta(&storage.sessions_dir, session_id).await?;
+    let branch = branch_path(&messages, meta.active_leaf.as_deref());
+
+    let Some(last) = branch.last() else {
+        return Ok(None);
+    };
+    if query.since.is_some_and(|since| last.timestamp < since) || query.until.is_some_and(|until| last.timestamp > until) {
+        return Ok(None);
+    }
+    if query.rating.is_some_and(|rating| !rating.matches(&branch)) {
+        return Ok(None);
+    }
+
+    // [ทุกคำตอบใน branch ต้องเป็น persona เดียวกัน ไม่งั้น system prompt จะไม่ตรงกับคำตอบ]
+    let mut personas = branch.iter()
+        .filter(|m| m.role == "assistant")
+        .map(|m| m.persona.as_deref().or(meta.persona.as_deref()).unwrap_or(DEFAULT_PERSONA));
+    let Some(persona_id) = personas.next() else {
+        return Ok(None);
+    };
+    if personas.any(|p| p != persona_id) || query.persona.as_deref().is_some_and(|p| p != persona_id) {
+        return Ok(None);
+    }
+    let Some(persona) = state.config.personas.get(persona_id) else {
+        return Ok(None);
+    };
+
+    let Some(example) = fine_tune_example(&persona.system_prompt, &branch, query.redact) else {
+        return Ok(None);
+    };
+
+    Ok(Some(serde_json::to_string(&example)? + "\n"))
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReindexResponse {
+    sessions: usize,
+}
+
+// -----------------------
+// POST /api/admin/search/reindex
+// sync full-text index กับ chat log ทั้งหมด (ไม่ล้าง index ระหว่างทำ)
+// -----------------------
+pub async fn reindex_search(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<Json<ReindexResponse>> {
+    require_admin(&state, &headers)?;
+
+    let sessions = reindex_all(&state)
+        .instrument(info_span!("admin.search_reindex"))
+        .await?;
+
+    Ok(Json(ReindexResponse { sessions }))
+}
@@ -4,53 +4,54 @@
 
 use axum::extract::State;
 use axum::Json;
-use axum_extra::extract::Multipart;
 use serde::Deserialize;
 use serde::Serialize;
 use tokio::fs;
-use uuid::Uuid;
+use crate::app::auth::Caller;
 use crate::app::error::AppError;
 use crate::app::result::AppResult;
 use crate::app::state::AppState;
 use crate::utils::embedding::create_embedding;
 use crate::utils::image::encode_image_to_base64;
 use crate::utils::image::ensure_dir_once;
-use crate::utils::image::get_ext_file_or_default;
-use crate::utils::image::get_filename_or_default;
 // use crate::utils::log::save_prompt_log;
-use crate::utils::qdrant::search_context_from_qdrant;
+use crate::app::persona::DEFAULT_PERSONA;
+use crate::utils::chat_input::{check_session_id, ChatImage, ChatInput};
+use crate::utils::completion::{complete_with_fallback, OpenAiUsage};
+use crate::utils::model_router::{Task, TaskRoute};
+use crate::utils::metrics::record_background_job;
+use crate::utils::qdrant::{search_context_from_qdrant, BranchScope};
 use crate::utils::qdrant::store_message_to_qdrant;
+use crate::utils::quota::off_duty_reply;
+use crate::utils::quota::{estimate_tokens, IMAGE_TOKENS_ESTIMATE};
+use crate::utils::quota::ExceededMode;
+use crate::utils::feedback::Feedback;
+use crate::utils::history;
+use crate::utils::session_lock::lock_session;
+use crate::utils::session_meta::{load_meta, save_meta};
+use crate::utils::session_title::auto_title;
 use crate::utils::summarizer::summarize_history;
 use std::sync::Arc;
-use std::fs::File;
-use std::io::Write;
 use chrono::{DateTime, Utc};
 use std::path::Path;
+use tracing::{info_span, warn, Instrument, Span};
+use uuid::Uuid;
 
 
-#[derive(Deserialize, Debug)]
-struct OpenAiResponse {
-    choices: Vec<OpenAiResponseChoice>,
-}
-
-#[derive(Deserialize, Debug)]
-struct OpenAiResponseChoice {
-    message: ChoiceMessage,
-}
-
-#[derive(Deserialize, Debug)]
-struct ChoiceMessage {
-    content: String,
-}
-
-#[derive(Deserialize, Debug)]
-struct OpenAiErrorResponse {
-    message: String,
+#[derive(Serialize, Debug)]
+pub struct ChatResponse {
+    pub reply: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ReplyMeta>,
 }
 
 #[derive(Serialize, Debug)]
-pub struct ChatResponse {
-    reply: String,
+pub struct ReplyMeta {
+    pub persona: String,
+    pub provider: String,
+    pub model: String,
+    // [id ของข้อความ assistant ใช้กับ regenerate/feedback]
+    pub message_id: String,
 }
 
 #[derive(Serialize, Debug)]
@@ -72,89 +73,204 @@ pub struct MessageRequest {
     content: Vec<ContentItem>,
 }
 
-#[derive(Serialize, Debug)]
-struct RequestBody {
-    model: String,
-    messages: Vec<MessageRequest>,
+impl MessageRequest {
+    pub fn text(role: &str, text: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: vec![ContentItem::Text { text: text.to_string() }],
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ChatMessage {
+    // [log เก่าไม่มี id/parent_id จะถูกเติมในหน่วยความจำตอนโหลด (ไม่เขียนกลับ) และลงไฟล์เมื่อ session ถูกแก้ครั้งถัดไป ดู history::normalize]
+    #[serde(default)]
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
     pub session_id: String,
     pub role: String,
     pub content: String,
     pub timestamp: DateTime<Utc>,
+    // [path ของรูปใน images_dir หรือ URL ภายนอก]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    // [คำตอบเก่าที่ถูก regenerate ทับ เรียงจากเก่าไปใหม่]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub swipes: Vec<Swipe>,
+    // [hash ของ system prompt ตอนที่ตอบ ดู PersonaConfig::prompt_version]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<Feedback>,
+    // [stream ขาดกลางทาง content คือส่วนที่ได้มาก่อน error]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Swipe {
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<Feedback>,
+}
+
+impl ChatMessage {
+    pub fn new(id: &str, session_id: &str, role: &str, content: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            parent_id: None,
+            session_id: session_id.to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            attachments: Vec::new(),
+            persona: None,
+            swipes: Vec::new(),
+            prompt_version: None,
+            feedback: None,
+            interrupted: false,
+        }
+    }
+}
+
+pub fn new_message_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+// [ผลของการเตรียม prompt: พร้อมส่งให้โมเดล หรือโควต้าหมด (persona mode)]
+pub enum PreparedTurn {
+    Ready(Box<ChatTurn>),
+    OffDuty,
+}
+
+// [ข้อมูลของ 1 turn ที่ต้องใช้ตั้งแต่เรียกโมเดลจนถึง background job]
+pub struct ChatTurn {
+    pub session_id: String,
+    pub user_id: String,
+    pub persona_id: String,
+    pub message: String,
+    pub task: Task,
+    pub route: TaskRoute,
+    pub messages: Vec<MessageRequest>,
+    pub attachments: Vec<String>,
+    // [ข้อความสุดท้ายของ branch ก่อน turn นี้]
+    pub parent_id: Option<String>,
+    // [regenerate จะใช้ id เดิมเพื่อแทนที่ข้อความ/point เดิม]
+    pub user_message_id: String,
+    pub reply_id: String,
+    // [session ใหม่: user ที่จะเป็นเจ้าของ บันทึกลง metadata พร้อม turn แรก]
+    pub owner: Option<String>,
+    pub(crate) user_embedding: Vec<f32>,
+}
+
+#[tracing::instrument(name = "chat", skip_all, fields(session_id = tracing::field::Empty))]
 pub async fn chat(
     State(state): State<Arc<AppState>>,
-    mut multipart: Multipart
+    caller: Caller,
+    input: ChatInput,
 ) -> AppResult<Json<ChatResponse>> {
-    let api_key = &state.openai_key;
-    let model = state.openai_model.to_string();
-    let client = &state.http.clone();
-
-    let mut message = String::new();
-    let mut image_path: Option<String> = None;
-    let mut session_id: Option<String> = None;
-
-    while let Some(field) = multipart.next_field().await? {
-        match field.name().unwrap_or_default() {
-            "message" => {
-                message = field.text().await.map_err(
-                    |e| AppError::BadRequest(format!("Invalid text: {e}"))
-                )?;
-            }
-            "session_id" => {
-                session_id = Some(field.text().await.unwrap_or_default());
-            }
-            "image" => {
-                let filename_raw = get_filename_or_default(&field)?;
-                let ext = get_ext_file_or_default(&filename_raw)?;
-                
-                let data = field.bytes().await?;
-
-                if data.is_empty() {
-                    continue;
-                }
+    Span::current().record("session_id", input.session_id.as_str());
 
-                let kind = infer::get(&data)
-                    .ok_or_else(|| AppError::BadRequest("Unknown file type".into()))?;
+    let turn = match prepare_turn(&state, &caller, input).await? {
+        PreparedTurn::Ready(turn) => *turn,
+        PreparedTurn::OffDuty => return Ok(Json(ChatResponse { reply: off_duty_reply(), meta: None })),
+    };
 
-                if !kind.mime_type().starts_with("image/") {
-                    return Err(AppError::BadRequest("Uploaded file is not an image".into()));
-                }
+    Ok(Json(reply_to_turn(&state, turn).await?))
+}
 
-                let id = Uuid::new_v4();
-                let filename = format!("chat-{}.{}", id, ext);
-                let filepath = format!("images/chat/{}", filename);
+// [เรียกโมเดล + บันทึก turn ใหม่ต่อท้าย branch]
+pub async fn reply_to_turn(state: &Arc<AppState>, turn: ChatTurn) -> AppResult<ChatResponse> {
+    let completion = complete_with_fallback(state, turn.task, &turn.route, &turn.messages).await?;
+    let reply = completion.reply;
+
+    persist_turn(state.clone(), &turn, &reply, completion.usage).await?;
+
+    Ok(ChatResponse {
+        reply,
+        meta: Some(ReplyMeta {
+            persona: turn.persona_id,
+            provider: completion.provider,
+            model: completion.model,
+            message_id: turn.reply_id,
+        }),
+    })
+}
 
-                let tmp_path = format!("images/chat/.tmp-{}", filename);
-                let mut tmp_file = File::create(&tmp_path)?;
-                tmp_file.write_all(&data)?;
-                tokio::fs::rename(tmp_path, &filepath).await?;
+// -----------------
+// เตรียม prompt: persona + ประวัติ/สรุป + context จาก Qdrant + ข้อความใหม่
+// ใช้ร่วมกันระหว่าง /api/chat และ /v1/chat/completions
+// -----------------
+pub async fn prepare_turn(state: &Arc<AppState>, caller: &Caller, mut input: ChatInput) -> AppResult<PreparedTurn> {
+    let meta = load_meta(&state.config.storage.sessions_dir, &input.session_id).await?;
+    let messages = load_full_messages(&state.config.storage.chat_logs_dir, &input.session_id)
+        .instrument(info_span!("chat.history_load"))
+        .await?;
 
-                if !data.is_empty() {
-                    image_path = Some(filepath);
-                }
-            }
-            _ => {}
-        }
+    // [session ที่มีอยู่แล้วต้องเป็นของผู้เรียก ตอบ 404 เหมือน /api/sessions/{id}]
+    if !messages.is_empty() && !caller.owns(meta.owner.as_deref()) {
+        return Err(AppError::NotFound(format!("Session '{}' not found", input.session_id)));
+    }
+    let history = history::branch_path(&messages, meta.active_leaf.as_deref());
+
+    if input.persona.is_none() {
+        input.persona = meta.persona;
     }
 
-    let session_id = session_id.ok_or_else(|| {
-        AppError::BadRequest("Missing session_id".into())
-    })?;
+    let mut prepared = prepare_turn_with_history(state, input, history, &message_ids(&messages)).await?;
+    if let PreparedTurn::Ready(turn) = &mut prepared {
+        turn.owner = messages.is_empty().then(|| caller.user_id.clone());
+    }
 
-    let user_embedding = create_embedding(api_key, &message).await?;
+    Ok(prepared)
+}
 
-    let mut messages: Vec<MessageRequest> = Vec::new();
-    messages.push(system_prompt_message());
+// [history = branch ก่อนหน้า turn นี้ ทั้งสรุปและ Qdrant จะเห็นแค่ข้อความใน branch นี้]
+// [logged_ids = id ของทุกข้อความใน log ใช้แยก point เก่าที่ไม่มีใน log ออกจากข้อความของ branch อื่น]
+pub async fn prepare_turn_with_history(
+    state: &Arc<AppState>,
+    input: ChatInput,
+    mut history: Vec<ChatMessage>,
+    logged_ids: &[String],
+) -> AppResult<PreparedTurn> {
+    let limits = &state.config.limits;
+
+    let ChatInput { session_id, message, user_id, persona: persona_id, images, params } = input;
+
+    let persona_id = persona_id.unwrap_or_else(|| DEFAULT_PERSONA.to_string());
+    let persona = state.config.persona(&persona_id)?;
+    let (task, route) = state.router.chat_route(persona, !images.is_empty(), &params)?;
+
+    if let Err(breach) = state.quota.check(&user_id) {
+        return match state.quota.settings().mode {
+            ExceededMode::Persona => Ok(PreparedTurn::OffDuty),
+            ExceededMode::Error => Err(breach.into_error()),
+        };
+    }
+
+    let user_embedding = create_embedding(state, &user_id, &message)
+        .instrument(info_span!("chat.embedding"))
+        .await?;
+
+    let parent_id = history.last().map(|m| m.id.clone());
+    let branch_ids = message_ids(&history);
 
-    let full_messages = load_full_messages(&session_id).await?;
+    let mut messages: Vec<MessageRequest> = Vec::new();
+    messages.push(system_prompt_message(&persona.system_prompt));
 
-    if full_messages.len() > 50 {
-        let summary = summarize_history(&session_id, &state.qdrant_client, &api_key, &model).await?;
+    if history.len() > limits.summary_threshold {
+        let summary = summarize_history(&session_id, &user_id, &history, state)
+            .instrument(info_span!("chat.summary"))
+            .await?;
 
         let summary_prompt = format!(
             "ก่อนหน้านี้มีบทสนทนาเยอะ จึงมีการสรุปไว้ดังนี้:\n{}\nกรุณาใช้บริบทนี้ในการตอบ",
@@ -166,7 +282,8 @@ pub async fn chat(
             content: vec![ContentItem::Text { text: summary_prompt }]
         });
 
-        let recent_messages = load_last_messages(&session_id, 15).await?;
+        history.sort_by_key(|m| m.timestamp);
+        let recent_messages = history.split_off(history.len() - limits.recent_messages);
         for msg in recent_messages {
             messages.push(MessageRequest {
                 role: msg.role,
@@ -174,7 +291,7 @@ pub async fn chat(
             });
         }
     } else {
-        for msg in full_messages {
+        for msg in history {
             messages.push(MessageRequest {
                 role: msg.role,
                 content: vec![ContentItem::Text { text: msg.content }],
@@ -184,9 +301,15 @@ pub async fn chat(
 
     let qdrant_messages = search_context_from_qdrant(
         &state.qdrant_client,
+        &state.config.qdrant,
         &session_id,
-        user_embedding.clone()
-    ).await?;
+        BranchScope { branch_ids: &branch_ids, logged_ids },
+        &message,
+        user_embedding.clone(),
+        limits.search_limit,
+    )
+    .instrument(info_span!("chat.qdrant_search"))
+    .await?;
 
     for msg in qdrant_messages {
         messages.push(MessageRequest {
@@ -199,13 +322,18 @@ pub async fn chat(
         text: message.clone()
     }];
 
-    if let Some(path) = &image_path {
-        if Path::new(path).exists() {
-            let image_data = encode_image_to_base64(path).await?;
-            user_content.push(ContentItem::ImageUrl {
-                image_url: ImageUrl { url: image_data }
-            });
-        }
+    let mut attachments = Vec::new();
+    for image in &images {
+        attachments.push(match image {
+            ChatImage::Stored(path) | ChatImage::Url(path) => path.clone(),
+        });
+        let url = match image {
+            ChatImage::Stored(path) => encode_image_to_base64(path).await?,
+            ChatImage::Url(url) => url.clone(),
+        };
+        user_content.push(ContentItem::ImageUrl {
+            image_url: ImageUrl { url }
+        });
     }
 
     messages.push(MessageRequest {
@@ -214,126 +342,185 @@ pub async fn chat(
     });
 
     // save_prompt_log(&session_id, &messages).await?;
-    
-    let req_body = RequestBody {
-        model,
-        messages
+
+    Ok(PreparedTurn::Ready(Box::new(ChatTurn {
+        session_id,
+        user_id,
+        persona_id,
+        message,
+        task,
+        route,
+        messages,
+        attachments,
+        parent_id,
+        user_message_id: new_message_id(),
+        reply_id: new_message_id(),
+        owner: None,
+        user_embedding,
+    })))
+}
+
+// -----------------
+// บันทึก turn: chat log + active branch เขียนก่อนตอบ (regenerate/feedback ทันทีจะเจอข้อความ)
+// ส่วน quota, Qdrant, full-text index และชื่อ session ทำเป็น background job
+// -----------------
+pub async fn persist_turn(state: Arc<AppState>, turn: &ChatTurn, reply: &str, usage: Option<OpenAiUsage>) -> AppResult<()> {
+    save_turn(state, turn, reply, usage, false).await
+}
+
+// [stream ขาดกลางทาง: provider คิดเงินไปแล้วก็ต้องหักโควต้า (ไม่ได้ usage มาให้ประมาณเอง)
+//  และบันทึก turn พร้อมคำตอบเท่าที่ได้ ไม่ให้ข้อความของ user หายไป]
+pub async fn persist_interrupted_turn(
+    state: Arc<AppState>,
+    turn: &ChatTurn,
+    partial_reply: &str,
+    usage: Option<OpenAiUsage>,
+) -> AppResult<()> {
+    let usage = usage.unwrap_or_else(|| estimate_usage(&turn.messages, partial_reply));
+    save_turn(state, turn, partial_reply, Some(usage), true).await
+}
+
+fn estimate_usage(messages: &[MessageRequest], reply: &str) -> OpenAiUsage {
+    let prompt_tokens = messages.iter()
+        .flat_map(|m| &m.content)
+        .map(|item| match item {
+            ContentItem::Text { text } => estimate_tokens(text),
+            ContentItem::ImageUrl { .. } => IMAGE_TOKENS_ESTIMATE,
+        })
+        .sum();
+
+    OpenAiUsage { prompt_tokens, completion_tokens: estimate_tokens(reply) }
+}
+
+async fn save_turn(
+    state: Arc<AppState>,
+    turn: &ChatTurn,
+    reply: &str,
+    usage: Option<OpenAiUsage>,
+    interrupted: bool,
+) -> AppResult<()> {
+    let user_id = turn.user_id.clone();
+    let user_message_id = turn.user_message_id.clone();
+    let reply_id = turn.reply_id.clone();
+    let persona_id = turn.persona_id.clone();
+    let session_id_bg = turn.session_id.clone();
+    let reply_bg = reply.to_string();
+    let user_embedding_bg = turn.user_embedding.clone();
+
+    let user_message = ChatMessage {
+        parent_id: turn.parent_id.clone(),
+        attachments: turn.attachments.clone(),
+        ..ChatMessage::new(&user_message_id, &session_id_bg, "user", &turn.message)
+    };
+    let reply_message = ChatMessage {
+        parent_id: Some(user_message_id.clone()),
+        prompt_version: state.config.personas.get(&persona_id).map(|p| p.prompt_version()),
+        persona: Some(persona_id),
+        interrupted,
+        ..ChatMessage::new(&reply_id, &session_id_bg, "assistant", &reply_bg)
     };
 
-    let raw = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .bearer_auth(api_key.clone())
-        .json(&req_body)
-        .send()
-        .await?
-        .text()
-        .await?;
+    // [หักโควต้าก่อนเขียน log เขียนไม่สำเร็จก็ยังนับ token ที่ใช้ไปแล้ว]
+    if let Some(usage) = usage {
+        state.quota.record(&user_id, usage.prompt_tokens, usage.completion_tokens);
+    }
+
+    // [user + assistant] -> log file, คำตอบใหม่กลายเป็นปลาย branch ที่ใช้อยู่
+    let owner = {
+        let _lock = lock_session(&session_id_bg).await;
+        save_messages(
+            &state.config.storage.chat_logs_dir,
+            &session_id_bg,
+            vec![user_message.clone(), reply_message.clone()],
+        ).await?;
+
+        let mut meta = load_meta(&state.config.storage.sessions_dir, &session_id_bg).await?;
+        meta.active_leaf = Some(reply_id.clone());
+        if meta.owner.is_none() {
+            meta.owner = turn.owner.clone();
+        }
+        save_meta(&state.config.storage.sessions_dir, &session_id_bg, &meta).await?;
+        meta.owner().to_string()
+    };
+
+    tokio::spawn(async move {
+        let mut ok = true;
 
-    if let Ok(res) = serde_json::from_str::<OpenAiResponse>(&raw) {
-        let reply = res.choices.get(0)
-            .map(|choices: &OpenAiResponseChoice| choices.message.content.clone())
-            .unwrap_or_else(|| "No response".to_string());
-
-        // -----------------
-        // BACKGROUND JOB
-        // เขียนไฟล์ + upsert Qdrant แบบไม่บล็อกการตอบ
-        // -----------------
-        {
-            let state = state.clone();
-            let session_id_bg = session_id.clone();
-            let message_bg = message.clone();
-            let reply_bg = reply.clone();
-            let user_embedding_bg = user_embedding.clone();
-
-            tokio::spawn(async move {
-                // [user: message] -> log file
-                let _ = save_message(ChatMessage {
-                    session_id: session_id_bg.clone(),
-                    role: "user".to_string(),
-                    content: message_bg.clone(),
-                    timestamp: Utc::now(),
-                }).await;
-
-                // [user: embedding] -> Qdrant (ใช้ embedding ที่คำนวณแล้ว)
-                let _ = store_message_to_qdrant(
-                    &state.qdrant_client,
-                    &session_id_bg,
-                    "user",
-                    &message_bg,
-                    user_embedding_bg,
-                    Utc::now().timestamp(),
-                ).await;
-
-                // [assistant: message] -> log file
-                let _ = save_message(ChatMessage {
-                    session_id: session_id_bg.clone(),
-                    role: "assistant".to_string(),
-                    content: reply_bg.clone(),
-                    timestamp: Utc::now(),
-                }).await;
-
-                // [assistant: embedding] -> Qdrant
-                if let Ok(assistant_embedding) = create_embedding(&state.openai_key, &reply_bg).await {
-                    let _ = store_message_to_qdrant(
+        // [usage] -> quota file
+        if usage.is_some() {
+            if let Err(e) = state.quota.persist().await {
+                warn!(error = %e, "Failed to persist quota usage");
+                ok = false;
+            }
+        }
+
+        // [user: embedding] -> Qdrant (ใช้ embedding ที่คำนวณแล้ว)
+        if let Err(e) = store_message_to_qdrant(
+            &state.qdrant_client,
+            &state.config.qdrant,
+            &user_message,
+            user_embedding_bg,
+        ).await {
+            warn!(error = %e, "Failed to store user embedding");
+            ok = false;
+        }
+
+        // [user + assistant] -> full-text index
+        if let Err(e) = state.search.index(&owner, vec![user_message, reply_message.clone()]).await {
+            warn!(error = %e, "Failed to index messages for search");
+            ok = false;
+        }
+
+        // [assistant: embedding] -> Qdrant (คำตอบที่ขาดกลางทางไม่เก็บเป็น memory)
+        if !interrupted {
+            match create_embedding(&state, &user_id, &reply_bg).await {
+                Ok(assistant_embedding) => {
+                    if let Err(e) = store_message_to_qdrant(
                         &state.qdrant_client,
-                        &session_id_bg,
-                        "assistant",
-                        &reply_bg,
+                        &state.config.qdrant,
+                        &reply_message,
                         assistant_embedding,
-                        Utc::now().timestamp(),
-                    ).await;
+                    ).await {
+                        warn!(error = %e, "Failed to store assistant embedding");
+                        ok = false;
+                    }
                 }
-            });
+                Err(e) => {
+                    warn!(error = %e, "Failed to embed assistant reply");
+                    ok = false;
+                }
+            }
         }
-        // -----------------
-        // BACKGROUND JOB ...End
-        // -----------------
-
-        Ok(Json(ChatResponse { reply }))
-    } 
-    else if let Ok(err) = serde_json::from_str::<OpenAiErrorResponse>(&raw) {
-        Err(AppError::InternalError(format!("OpenAI error: {}", err.message)))
-    } 
-    else {
-        Err(AppError::InternalError("Unknown response format".into()))
-    }
+
+        record_background_job("chat_persist", ok);
+
+        // [ตั้งชื่อ session หลังบันทึกเสร็จ ให้เห็น turn ล่าสุดด้วย]
+        match auto_title(&state, &user_id, &session_id_bg).instrument(info_span!("chat.title")).await {
+            Ok(true) => record_background_job("session_title", true),
+            Ok(false) => {}
+            Err(e) => {
+                warn!(error = %e, "Failed to generate session title");
+                record_background_job("session_title", false);
+            }
+        }
+    }.instrument(info_span!("chat.background_job")));
+
+    Ok(())
 }
 
-fn system_prompt_message() -> MessageRequest {
-    MessageRequest {
-        role: "system".to_string(),
-        content: vec![ContentItem::Text {
-            text: "
-                - คุณคือ 'ราพี (Rapi)' จากเกม Goddess of Victory: NIKKE  
-                - กำลังคุยแชทกับผู้บัญชาการ
-                - อย่าแสดงตัวว่าเป็น AI
-                - พูดภาษาผู้หญิงเท่านั้น
-                - ถ้าไม่มีข้อมูลใหม่ ให้ตอบต่อเนื่องตามประวัติสนทนาล่าสุด
-                - อย่าเปลี่ยนหัวข้อสนทนาเอง
-                - คุณเป็นผู้นำทีม Counters ที่เย็นชา สุขุม และไว้ใจได้
-                - พูดด้วยน้ำเสียงผู้หญิงที่สุภาพและมีความรู้สึกต่อเนื่องกับสิ่งที่เคยคุยมาก่อนหน้า  
-                - ราพีชอบผู้บัญชาการ
-            "
-            .to_string()
-        }]
-    }
+fn system_prompt_message(system_prompt: &str) -> MessageRequest {
+    MessageRequest::text("system", system_prompt)
 }
 
-pub async fn save_message(message: ChatMessage) -> AppResult<()> {
-    let dir_path = "data/chat_logs";
-    let file_path = format!("{}/{}.json", dir_path, message.session_id);
+// [ต่อท้าย log ผู้เรียกต้องถือ lock_session ของ session นี้]
+pub async fn save_messages(dir_path: &str, session_id: &str, new_messages: Vec<ChatMessage>) -> AppResult<()> {
+    let file_path = format!("{}/{}.json", dir_path, session_id);
 
     ensure_dir_once(dir_path)?;
 
-    let mut messages: Vec<ChatMessage> = if Path::new(&file_path).exists() {
-        let content = fs::read_to_string(&file_path).await?;
-        serde_json::from_str(&content).unwrap_or_default()
-    }
-    else {
-        Vec::new()
-    };
+    let mut messages = load_full_messages(dir_path, session_id).await?;
 
-    messages.push(message);
+    messages.extend(new_messages);
 
     let json = serde_json::to_string_pretty(&messages)?;
     fs::write(&file_path, json).await?;
@@ -341,35 +528,66 @@ pub async fn save_message(message: ChatMessage) -> AppResult<()> {
     Ok(())
 } 
 
-pub async fn load_last_messages(session_id: &str, limit: usize) -> AppResult<Vec<ChatMessage>> {
-    let dir_path = "data/chat_logs";
+// [เขียนทับทั้งไฟล์ ใช้ตอนแก้ไขข้อความเดิม เช่น regenerate ผู้เรียกต้องถือ lock_session]
+pub async fn write_messages(dir_path: &str, session_id: &str, messages: &[ChatMessage]) -> AppResult<()> {
     let file_path = format!("{}/{}.json", dir_path, session_id);
 
-    if !Path::new(&file_path).exists() {
-        return Ok(vec![]);
-    }
+    ensure_dir_once(dir_path)?;
 
-    let content = fs::read_to_string(&file_path).await?;
-    let mut all_msgs: Vec<ChatMessage> = serde_json::from_str(&content)?;
+    let json = serde_json::to_string_pretty(messages)?;
+    fs::write(&file_path, json).await?;
+
+    Ok(())
+}
 
-    all_msgs.sort_by_key(|m| m.timestamp); 
+// [session = ไฟล์ {id}.json ใน chat_logs_dir ข้ามไฟล์ที่ชื่อไม่ผ่าน check_session_id]
+pub async fn list_session_ids(dir_path: &str) -> AppResult<Vec<String>> {
+    let mut ids = Vec::new();
+
+    let mut dir = match fs::read_dir(dir_path).await {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ids),
+        Err(e) => return Err(e.into()),
+    };
 
-    if all_msgs.len() > limit {
-        all_msgs = all_msgs[all_msgs.len()-limit..].to_vec();
+    while let Some(entry) = dir.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        if let Some(session_id) = path.file_stem().and_then(|s| s.to_str()) {
+            if check_session_id(session_id).is_ok() {
+                ids.push(session_id.to_string());
+            }
+        }
     }
 
-    Ok(all_msgs)
+    Ok(ids)
 }
 
-pub async fn load_full_messages(session_id: &str) -> AppResult<Vec<ChatMessage>> {
-    let file_path = format!("data/chat_logs/{}.json", session_id);
+pub async fn load_full_messages(dir_path: &str, session_id: &str) -> AppResult<Vec<ChatMessage>> {
+    let file_path = format!("{}/{}.json", dir_path, session_id);
 
     if !Path::new(&file_path).exists() {
         return Ok(vec![]);
     }
 
     let content = fs::read_to_string(&file_path).await?;
-    let messages: Vec<ChatMessage> = serde_json::from_str(&content)?;
+    let mut messages: Vec<ChatMessage> = serde_json::from_str(&content)?;
+
+    // [ไม่เขียนกลับตอนอ่าน id ที่เติมให้จะถูกบันทึกเมื่อ session ถูกแก้ครั้งถัดไป]
+    history::normalize(session_id, &mut messages);
 
     Ok(messages)
 }
+
+pub fn message_ids(messages: &[ChatMessage]) -> Vec<String> {
+    messages.iter().map(|m| m.id.clone()).collect()
+}
+
+pub async fn load_active_branch(state: &AppState, session_id: &str) -> AppResult<Vec<ChatMessage>> {
+    let messages = load_full_messages(&state.config.storage.chat_logs_dir, session_id).await?;
+    let meta = load_meta(&state.config.storage.sessions_dir, session_id).await?;
+
+    Ok(history::branch_path(&messages, meta.active_leaf.as_deref()))
+}
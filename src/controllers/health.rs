@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::app::state::AppState;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CheckResult {
+    ok: bool,
+    latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cached: bool,
+}
+
+impl CheckResult {
+    fn from_result(started: Instant, result: Result<(), String>) -> Self {
+        Self {
+            ok: result.is_ok(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            error: result.err(),
+            cached: false,
+        }
+    }
+}
+
+pub async fn healthz() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+pub async fn readyz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Value>) {
+    let storage = &state.config.storage;
+
+    let mut checks = serde_json::Map::new();
+    checks.insert("qdrant".into(), json!(check_qdrant(&state).await));
+    checks.insert("chat_logs_dir".into(), json!(check_writable(&storage.chat_logs_dir).await));
+    checks.insert("images_dir".into(), json!(check_writable(&storage.images_dir).await));
+
+    if state.config.health.check_llm {
+        checks.insert("llm".into(), json!(check_llm(&state).await));
+    }
+
+    let ready = checks.values().all(|c| c["ok"].as_bool().unwrap_or(false));
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (status, Json(json!({
+        "status": if ready { "ok" } else { "unavailable" },
+        "checks": checks,
+    })))
+}
+
+async fn check_qdrant(state: &AppState) -> CheckResult {
+    let started = Instant::now();
+    let result = match state.qdrant_client.collection_exists(&state.config.qdrant.collection).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(format!("Collection '{}' not found", state.config.qdrant.collection)),
+        Err(e) => Err(e.to_string()),
+    };
+
+    CheckResult::from_result(started, result)
+}
+
+// [เขียนไฟล์ทดสอบแล้วลบทิ้ง เพื่อให้รู้ว่า volume ยังเขียนได้จริง]
+async fn check_writable(dir: &str) -> CheckResult {
+    let started = Instant::now();
+    let probe = format!("{}/.readyz-{}", dir, Uuid::new_v4());
+
+    let result = async {
+        tokio::fs::create_dir_all(dir).await?;
+        tokio::fs::write(&probe, b"ok").await?;
+        tokio::fs::remove_file(&probe).await
+    }
+    .await
+    .map_err(|e| format!("{dir}: {e}"));
+
+    CheckResult::from_result(started, result)
+}
+
+// [เรียก /models ซึ่งไม่เสียเงิน และ cache ผลไว้ กันยิง provider ทุกครั้งที่ Fly เช็ค]
+async fn check_llm(state: &AppState) -> CheckResult {
+    let ttl = Duration::from_secs(state.config.health.llm_cache_secs);
+
+    if let Some((at, cached)) = state.llm_health.lock().unwrap().clone() {
+        if at.elapsed() < ttl {
+            return CheckResult { cached: true, ..cached };
+        }
+    }
+
+    let started = Instant::now();
+    let result = if state.openai.breaker().is_open() {
+        Err("Circuit breaker is open".to_string())
+    } else {
+        match state.openai.get("/models", Duration::from_secs(5)).await {
+            Ok(res) if res.status().is_success() => Ok(()),
+            Ok(res) => Err(format!("Provider returned {}", res.status())),
+            Err(e) => Err(e.to_string()),
+        }
+    };
+
+    let check = CheckResult::from_result(started, result);
+    *state.llm_health.lock().unwrap() = Some((Instant::now(), check.clone()));
+
+    check
+}
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+use crate::app::state::AppState;
+
+// -----------------------
+// GET /api/images/{filename}
+// รูปที่แนบมากับข้อความ (อยู่ใน storage.images_dir)
+// ไม่เช็คเจ้าของ: URL นี้ใช้ใน <img> ซึ่งส่ง Authorization ไม่ได้ ชื่อไฟล์เป็น uuid เดาไม่ได้
+// ใครได้ URL ไปก็เปิดรูปได้ (capability URL) เหมือนลิงก์แชร์
+// -----------------------
+pub async fn get_image(
+    State(state): State<Arc<AppState>>,
+    Path(filename): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    if filename.starts_with('.') || filename.contains('/') || filename.contains('\\') || filename.contains("..") {
+        return Err(AppError::BadRequest("Invalid image name".into()));
+    }
+
+    let path = format!("{}/{}", state.config.storage.images_dir, filename);
+    let data = match tokio::fs::read(&path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AppError::NotFound(format!("Image '{filename}' not found")));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mime = infer::get(&data)
+        .map(|kind| kind.mime_type())
+        .unwrap_or("application/octet-stream");
+
+    // [ชื่อไฟล์เป็น uuid ไม่เปลี่ยนเนื้อหา cache ได้นาน]
+    Ok((
+        [
+            (header::CONTENT_TYPE, mime),
+            (header::CACHE_CONTROL, "private, max-age=31536000, immutable"),
+        ],
+        data,
+    ))
+}
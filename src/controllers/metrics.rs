@@ -0,0 +1,14 @@
+use axum::http::header;
+use axum::response::IntoResponse;
+
+use crate::app::result::AppResult;
+use crate::utils::metrics::render;
+
+pub async fn metrics() -> AppResult<impl IntoResponse> {
+    let body = render()?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        body,
+    ))
+}
@@ -1 +1,8 @@
-pub mod chat;
\ No newline at end of file
+pub mod chat;
+pub mod admin;
+pub mod metrics;
+pub mod health;
+pub mod openai_compat;
+pub mod sessions;
+pub mod images;
+pub mod search;
@@ -0,0 +1,315 @@
+// [OpenAI-compatible API สำหรับ SillyTavern / Open WebUI / OpenAI SDK]
+// [model = persona, user หรือ X-Session-Id = session_id แล้ววิ่งผ่าน pipeline เดียวกับ /api/chat]
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_extra::extract::WithRejection;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{info_span, warn, Instrument, Span};
+use uuid::Uuid;
+
+use crate::app::auth::{identify, Caller};
+use crate::app::error::AppError;
+use crate::app::state::AppState;
+use crate::controllers::chat::{persist_interrupted_turn, persist_turn, prepare_turn, PreparedTurn};
+use crate::utils::chat_input::{resolve_images, ChatInput, SESSION_ID_HEADER};
+use crate::utils::completion::{complete_with_fallback, stream_with_fallback, GenerationParams, OpenAiUsage};
+use crate::utils::quota::off_duty_reply;
+use crate::utils::telemetry::current_request_id;
+
+#[derive(Deserialize, Debug)]
+pub struct ChatCompletionRequest {
+    #[serde(default)]
+    model: String,
+    messages: Vec<CompatMessage>,
+    #[serde(default)]
+    stream: bool,
+    user: Option<String>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    max_tokens: Option<u32>,
+    max_completion_tokens: Option<u32>,
+    presence_penalty: Option<f32>,
+    seed: Option<i64>,
+    stop: Option<StopField>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum StopField {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize, Debug)]
+struct CompatMessage {
+    role: String,
+    content: Option<CompatContent>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum CompatContent {
+    Text(String),
+    Parts(Vec<CompatPart>),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CompatPart {
+    Text { text: String },
+    ImageUrl { image_url: CompatImageUrl },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+struct CompatImageUrl {
+    url: String,
+}
+
+// -----------------------
+// Error ในรูปแบบของ OpenAI ({"error": {...}}) ให้ SDK อ่าน message ได้
+// -----------------------
+pub struct CompatError(AppError);
+
+impl From<AppError> for CompatError {
+    fn from(error: AppError) -> Self {
+        CompatError(error)
+    }
+}
+
+impl From<JsonRejection> for CompatError {
+    fn from(rejection: JsonRejection) -> Self {
+        CompatError(rejection.into())
+    }
+}
+
+impl IntoResponse for CompatError {
+    fn into_response(self) -> Response {
+        let error = self.0;
+        let status = error.status();
+
+        if status.is_server_error() {
+            tracing::error!(code = error.code(), error = %error, "Request failed");
+        }
+
+        let body = Json(json!({
+            "error": {
+                "message": error.to_string(),
+                "type": error.code(),
+                "code": error.code(),
+                "request_id": current_request_id(),
+            }
+        }));
+
+        (status, body).into_response()
+    }
+}
+
+pub async fn list_models(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let mut ids: Vec<&String> = state.config.personas.keys().collect();
+    ids.sort();
+
+    let data: Vec<Value> = ids.into_iter()
+        .map(|id| json!({
+            "id": id,
+            "object": "model",
+            "created": 0,
+            "owned_by": "rapi",
+            "name": state.config.personas[id].name,
+        }))
+        .collect();
+
+    Json(json!({ "object": "list", "data": data }))
+}
+
+#[tracing::instrument(name = "chat_completions", skip_all, fields(session_id = tracing::field::Empty))]
+pub async fn chat_completions(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    WithRejection(Json(body), _): WithRejection<Json<ChatCompletionRequest>, CompatError>,
+) -> Result<Response, CompatError> {
+    let caller = identify(&state.config, &headers)?;
+    let stream = body.stream;
+    let model = body.model.clone();
+    let input = to_chat_input(&state, &caller, &headers, body).await?;
+    Span::current().record("session_id", input.session_id.as_str());
+
+    let id = format!("chatcmpl-{}", Uuid::new_v4().simple());
+    let created = Utc::now().timestamp();
+
+    let turn = match prepare_turn(&state, &caller, input).await? {
+        PreparedTurn::Ready(turn) => *turn,
+        PreparedTurn::OffDuty => {
+            let reply = off_duty_reply();
+            return Ok(if stream {
+                let events = vec![
+                    chunk_event(&id, created, &model, json!({ "role": "assistant", "content": reply }), None),
+                    chunk_event(&id, created, &model, json!({}), Some("stop")),
+                    Event::default().data("[DONE]"),
+                ];
+                sse_response(events)
+            } else {
+                Json(completion_body(&id, created, &model, &reply, None)).into_response()
+            });
+        }
+    };
+
+    if !stream {
+        let completion = complete_with_fallback(&state, turn.task, &turn.route, &turn.messages).await?;
+        persist_turn(state.clone(), &turn, &completion.reply, completion.usage).await?;
+
+        return Ok(Json(completion_body(&id, created, &turn.persona_id, &completion.reply, completion.usage))
+            .into_response());
+    }
+
+    let mut upstream = stream_with_fallback(&state, turn.task, &turn.route, &turn.messages).await?;
+    let (tx, rx) = mpsc::channel::<Result<Event, Infallible>>(32);
+    let span = info_span!("chat.stream", provider = %upstream.provider, model = %upstream.model);
+
+    // [อ่าน stream จาก provider แล้วส่งต่อเป็น SSE ครบแล้วค่อยบันทึก (client ปิดกลางทางก็ยังบันทึก)]
+    tokio::spawn(async move {
+        let persona_id = turn.persona_id.clone();
+        let mut reply = String::new();
+
+        let _ = tx.send(Ok(chunk_event(&id, created, &persona_id, json!({ "role": "assistant" }), None))).await;
+
+        loop {
+            match upstream.next_delta().await {
+                Ok(Some(delta)) => {
+                    reply.push_str(&delta);
+                    let _ = tx.send(Ok(chunk_event(&id, created, &persona_id, json!({ "content": delta }), None))).await;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    warn!(error = %e, "Upstream stream failed");
+                    let _ = tx.send(Ok(Event::default().data(json!({
+                        "error": { "message": e.to_string(), "type": e.code(), "code": e.code() }
+                    }).to_string()))).await;
+
+                    if let Err(e) = persist_interrupted_turn(state.clone(), &turn, &reply, upstream.usage).await {
+                        warn!(error = %e, "Failed to save interrupted turn");
+                    }
+                    return;
+                }
+            }
+        }
+
+        let _ = tx.send(Ok(chunk_event(&id, created, &persona_id, json!({}), Some("stop")))).await;
+        let _ = tx.send(Ok(Event::default().data("[DONE]"))).await;
+
+        if let Err(e) = persist_turn(state.clone(), &turn, &reply, upstream.usage).await {
+            warn!(error = %e, "Failed to save streamed turn");
+        }
+    }.instrument(span));
+
+    Ok(Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default()).into_response())
+}
+
+// [ใช้แค่ข้อความ user ล่าสุด ประวัติก่อนหน้ามาจาก memory ของ server ไม่ใช่จาก client]
+async fn to_chat_input(
+    state: &AppState,
+    caller: &Caller,
+    headers: &HeaderMap,
+    body: ChatCompletionRequest,
+) -> Result<ChatInput, AppError> {
+    let session_id = headers.get(SESSION_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .filter(|s| !s.is_empty())
+        .or_else(|| body.user.clone().filter(|u| !u.is_empty()))
+        .ok_or_else(|| AppError::BadRequest(format!(
+            "Set the `user` field or the {SESSION_ID_HEADER} header to choose a session"
+        )))?;
+
+    let last_user = body.messages.into_iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .ok_or_else(|| AppError::BadRequest("messages must contain a user message".into()))?;
+
+    let mut texts: Vec<String> = Vec::new();
+    let mut raw_images: Vec<String> = Vec::new();
+    match last_user.content {
+        Some(CompatContent::Text(text)) => texts.push(text),
+        Some(CompatContent::Parts(parts)) => {
+            for part in parts {
+                match part {
+                    CompatPart::Text { text } => texts.push(text),
+                    CompatPart::ImageUrl { image_url } => raw_images.push(image_url.url),
+                    CompatPart::Other => {}
+                }
+            }
+        }
+        None => {}
+    }
+
+    let input = ChatInput {
+        session_id,
+        message: texts.join("\n"),
+        user_id: caller.user_id.clone(),
+        persona: Some(body.model).filter(|m| !m.is_empty()),
+        images: resolve_images(raw_images, &state.config.storage.images_dir).await?,
+        params: GenerationParams {
+            temperature: body.temperature,
+            top_p: body.top_p,
+            max_tokens: body.max_completion_tokens.or(body.max_tokens),
+            presence_penalty: body.presence_penalty,
+            seed: body.seed,
+            stop: body.stop.map(|stop| match stop {
+                StopField::One(s) => vec![s],
+                StopField::Many(v) => v,
+            }),
+        },
+    };
+    input.validate(state.config.limits.max_images)?;
+
+    Ok(input)
+}
+
+fn completion_body(id: &str, created: i64, model: &str, reply: &str, usage: Option<OpenAiUsage>) -> Value {
+    let usage = usage.map(|u| json!({
+        "prompt_tokens": u.prompt_tokens,
+        "completion_tokens": u.completion_tokens,
+        "total_tokens": u.prompt_tokens + u.completion_tokens,
+    }));
+
+    json!({
+        "id": id,
+        "object": "chat.completion",
+        "created": created,
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": reply },
+            "finish_reason": "stop",
+        }],
+        "usage": usage,
+    })
+}
+
+fn chunk_event(id: &str, created: i64, model: &str, delta: Value, finish_reason: Option<&str>) -> Event {
+    Event::default().data(json!({
+        "id": id,
+        "object": "chat.completion.chunk",
+        "created": created,
+        "model": model,
+        "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+    }).to_string())
+}
+
+fn sse_response(events: Vec<Event>) -> Response {
+    let stream = tokio_stream::iter(events.into_iter().map(Ok::<_, Infallible>));
+    Sse::new(stream).into_response()
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::Json;
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info_span, Instrument};
+
+use crate::app::auth::Caller;
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+use crate::app::state::AppState;
+use crate::utils::chat_input::check_session_id;
+use crate::utils::search_index::{SearchHit, SearchQuery};
+use crate::utils::session_meta::load_meta;
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+const MAX_SEARCH_LIMIT: usize = 100;
+
+#[derive(Deserialize, Debug)]
+pub struct SearchParams {
+    q: String,
+    // [admin เท่านั้น: ค้นใน session ของ user อื่น ไม่ใส่ = ทุก user]
+    owner: Option<String>,
+    session_id: Option<String>,
+    // [user | assistant]
+    role: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchResultHit {
+    #[serde(flatten)]
+    hit: SearchHit,
+    session_title: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchResponse {
+    total: usize,
+    hits: Vec<SearchResultHit>,
+}
+
+// -----------------------
+// GET /api/search?q=&session_id=&role=&since=&until=&limit=&offset=
+// ค้นทุกคำ (เว้นวรรคคั่น) ใน user/assistant ของทุก branch เฉพาะ session ของผู้เรียก
+// -----------------------
+pub async fn search(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    WithRejection(Query(params), _): WithRejection<Query<SearchParams>, AppError>,
+) -> AppResult<Json<SearchResponse>> {
+    let text = params.q.trim().to_string();
+    if text.is_empty() {
+        return Err(AppError::BadRequest("q must not be empty".into()));
+    }
+    if let Some(session_id) = &params.session_id {
+        check_session_id(session_id)?;
+    }
+    if params.role.as_deref().is_some_and(|r| r != "user" && r != "assistant") {
+        return Err(AppError::BadRequest("role must be 'user' or 'assistant'".into()));
+    }
+
+    // [admin ไม่ระบุ owner = ค้นทุก user]
+    let (owner, all_owners) = match params.owner {
+        Some(owner) if caller.admin => (owner, false),
+        Some(_) => return Err(AppError::Unauthorized("Only admin can search other users' sessions".into())),
+        None if caller.admin => (String::new(), true),
+        None => (caller.user_id, false),
+    };
+
+    let results = state.search
+        .search(SearchQuery {
+            text,
+            owner,
+            all_owners,
+            session_id: params.session_id,
+            role: params.role,
+            since: params.since,
+            until: params.until,
+            limit: params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT),
+            offset: params.offset.unwrap_or(0),
+        })
+        .instrument(info_span!("search.query"))
+        .await?;
+
+    // [ชื่อ session ให้หน้าผลค้นหาแสดงได้เลย อ่าน metadata ครั้งเดียวต่อ session]
+    let mut titles: HashMap<String, Option<String>> = HashMap::new();
+    let mut hits = Vec::with_capacity(results.hits.len());
+
+    for hit in results.hits {
+        if !titles.contains_key(&hit.session_id) {
+            let meta = load_meta(&state.config.storage.sessions_dir, &hit.session_id).await?;
+            titles.insert(hit.session_id.clone(), meta.title);
+        }
+        let session_title = titles[&hit.session_id].clone();
+        hits.push(SearchResultHit { hit, session_title });
+    }
+
+    Ok(Json(SearchResponse { total: results.total, hits }))
+}
@@ -0,0 +1,1068 @@
+use std::convert::Infallible;
+use std::path::Path as FsPath;
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use tokio::fs;
+use axum_extra::extract::WithRejection;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{info_span, warn, Instrument};
+
+use crate::app::auth::Caller;
+use crate::app::config::StorageConfig;
+use crate::app::error::AppError;
+use crate::app::persona::DEFAULT_PERSONA;
+use crate::app::result::AppResult;
+use crate::app::state::AppState;
+use crate::controllers::chat::{
+    list_session_ids, load_full_messages, message_ids, new_message_id, prepare_turn_with_history, reply_to_turn, write_messages, ChatMessage, ChatResponse,
+    PreparedTurn, ReplyMeta, Swipe,
+};
+use crate::utils::chat_input::{check_session_id, ChatImage, ChatInput};
+use crate::utils::completion::{complete_with_fallback, GenerationParams};
+use crate::utils::embedding::{create_embedding, create_embeddings};
+use crate::utils::metrics::record_background_job;
+use crate::utils::qdrant::{
+    delete_session_points, search_memory, store_message_to_qdrant, store_messages_to_qdrant, BranchScope,
+};
+use crate::utils::feedback::{Feedback, Rating};
+use crate::utils::export::{
+    export_filename, html_footer, html_header, html_message, markdown_header, markdown_message, role_label,
+    ExportFormat, SessionExport, EXPORT_VERSION,
+};
+use crate::utils::import::{parse_import, ImportFormat, SkippedEntry};
+use crate::utils::image::{attachment_url, encode_image_to_base64};
+use crate::utils::history::{branch_path, leaves, resolve_leaf};
+use crate::utils::quota::{off_duty_reply, QuotaBreach};
+use crate::utils::session_title::generate_title;
+use crate::utils::session_lock::lock_session;
+use crate::utils::session_meta::{delete_meta, load_meta, save_meta, set_active_leaf, set_title, SessionMeta};
+
+#[derive(Deserialize, Debug, Default)]
+pub struct RegenerateRequest {
+    // [ไม่ใส่ = persona เดิมของคำตอบนั้น]
+    persona: Option<String>,
+    #[serde(flatten)]
+    params: GenerationParams,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SessionSummary {
+    session_id: String,
+    title: Option<String>,
+    persona: Option<String>,
+    message_count: usize,
+    created_at: Option<DateTime<Utc>>,
+    last_activity: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SessionDetail {
+    #[serde(flatten)]
+    summary: SessionSummary,
+    active_leaf: Option<String>,
+    branch_count: usize,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SessionUpdate {
+    // [ส่ง null เพื่อล้างค่า]
+    #[serde(default, with = "double_option")]
+    title: Option<Option<String>>,
+    #[serde(default, with = "double_option")]
+    persona: Option<Option<String>>,
+}
+
+// [แยก "ไม่ส่ง field" (None) ออกจาก "ส่ง null" (Some(None))]
+mod double_option {
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Option::<T>::deserialize(deserializer).map(Some)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HistoryQuery {
+    // [id ของข้อความ เอาเฉพาะข้อความที่เก่ากว่าตัวนี้]
+    before: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Attachment {
+    url: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct HistoryMessage {
+    id: String,
+    parent_id: Option<String>,
+    role: String,
+    content: String,
+    timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    persona: Option<String>,
+    attachments: Vec<Attachment>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    swipes: Vec<Swipe>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feedback: Option<Feedback>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct HistoryPage {
+    // [เรียงจากเก่าไปใหม่]
+    messages: Vec<HistoryMessage>,
+    // [ส่งเป็น ?before= เพื่อดึงหน้าถัดไป (เก่ากว่า), null = หมดแล้ว]
+    next_before: Option<String>,
+}
+
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+const MAX_HISTORY_LIMIT: usize = 200;
+
+#[derive(Deserialize, Debug)]
+pub struct ExportQuery {
+    // [ไม่ใส่ = md]
+    format: Option<ExportFormat>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ImportQuery {
+    format: ImportFormat,
+    // [ตั้ง id เองได้เฉพาะไฟล์ที่มี session เดียว]
+    session_id: Option<String>,
+    // [persona ของข้อความ assistant ที่ไฟล์ไม่ได้บอกไว้]
+    persona: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportedSessionSummary {
+    session_id: String,
+    title: Option<String>,
+    message_count: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportResponse {
+    sessions: Vec<ImportedSessionSummary>,
+    // [แสดงแค่ MAX_REPORTED_SKIPS รายการแรก นับทั้งหมดใน skipped_total]
+    skipped: Vec<SkippedEntry>,
+    skipped_total: usize,
+}
+
+const MAX_REPORTED_SKIPS: usize = 100;
+const IMPORT_EMBED_BATCH: usize = 64;
+
+#[derive(Deserialize, Debug)]
+pub struct FeedbackRequest {
+    rating: Rating,
+    comment: Option<String>,
+}
+
+const MAX_FEEDBACK_COMMENT_CHARS: usize = 2000;
+
+#[derive(Deserialize, Debug)]
+pub struct MemorySearchQuery {
+    q: String,
+    limit: Option<u64>,
+    #[serde(default)]
+    scope: MemoryScope,
+}
+
+// [branch = ชุดเดียวกับที่ /api/chat ใช้ค้น, session = ทุก point ของ session รวม summary และ branch อื่น]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryScope {
+    #[default]
+    Branch,
+    Session,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryKind {
+    Message,
+    Summary,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MemorySearchHit {
+    message_id: String,
+    score: f32,
+    content: String,
+    role: String,
+    kind: MemoryKind,
+    timestamp: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MemorySearchResponse {
+    query: String,
+    scope: MemoryScope,
+    hits: Vec<MemorySearchHit>,
+}
+
+const MAX_MEMORY_SEARCH_LIMIT: u64 = 50;
+
+#[derive(Deserialize, Debug)]
+pub struct EditRequest {
+    message: String,
+    persona: Option<String>,
+    #[serde(flatten)]
+    params: GenerationParams,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SwitchBranchRequest {
+    // [id ของข้อความไหนก็ได้ในกิ่ง จะเลื่อนไปปลายกิ่งให้เอง]
+    message_id: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BranchSummary {
+    leaf_id: String,
+    length: usize,
+    updated_at: DateTime<Utc>,
+    preview: String,
+    active: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BranchesResponse {
+    active_leaf: Option<String>,
+    branches: Vec<BranchSummary>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BranchResponse {
+    active_leaf: String,
+    messages: Vec<ChatMessage>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RegenerateResponse {
+    #[serde(flatten)]
+    response: ChatResponse,
+    // [คำตอบก่อนหน้าทั้งหมดของข้อความนี้]
+    swipes: Vec<Swipe>,
+}
+
+fn summarize_session(session_id: &str, messages: &[ChatMessage], meta: &SessionMeta) -> SessionSummary {
+    SessionSummary {
+        session_id: session_id.to_string(),
+        title: meta.title.clone(),
+        // [ไม่ได้ตั้งไว้ให้ใช้ persona ของคำตอบล่าสุด]
+        persona: meta.persona.clone()
+            .or_else(|| messages.iter().rev().find_map(|m| m.persona.clone())),
+        message_count: messages.len(),
+        created_at: messages.iter().map(|m| m.timestamp).min(),
+        last_activity: messages.iter().map(|m| m.timestamp).max(),
+    }
+}
+
+// [session ของคนอื่นตอบ 404 เหมือนไม่มี ไม่บอกว่ามี id นี้อยู่]
+async fn load_session(state: &AppState, caller: &Caller, session_id: &str) -> AppResult<(Vec<ChatMessage>, SessionMeta)> {
+    check_session_id(session_id)?;
+
+    let meta = load_meta(&state.config.storage.sessions_dir, session_id).await?;
+    let messages = match caller.owns(meta.owner.as_deref()) {
+        true => load_full_messages(&state.config.storage.chat_logs_dir, session_id).await?,
+        false => Vec::new(),
+    };
+    if messages.is_empty() {
+        return Err(AppError::NotFound(format!("Session '{session_id}' not found")));
+    }
+
+    Ok((messages, meta))
+}
+
+// -----------------------
+// GET /api/sessions
+// session = ไฟล์ใน chat_logs_dir ที่ผู้เรียกเป็นเจ้าของ (admin เห็นทั้งหมด), เรียงตาม activity ล่าสุด
+// -----------------------
+pub async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> AppResult<Json<Vec<SessionSummary>>> {
+    let storage = &state.config.storage;
+    let mut sessions = Vec::new();
+
+    for session_id in list_session_ids(&storage.chat_logs_dir).await? {
+        let session_id = session_id.as_str();
+        // [เช็คเจ้าของจาก metadata (ไฟล์เล็ก) ก่อน โหลด log เฉพาะ session ของตัวเอง]
+        let meta = load_meta(&storage.sessions_dir, session_id).await?;
+        if !caller.owns(meta.owner.as_deref()) {
+            continue;
+        }
+        let messages = match load_full_messages(&storage.chat_logs_dir, session_id).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                warn!(session_id, error = %e, "Skipping unreadable chat log");
+                continue;
+            }
+        };
+
+        sessions.push(summarize_session(session_id, &messages, &meta));
+    }
+
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.last_activity));
+
+    Ok(Json(sessions))
+}
+
+pub async fn get_session(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+) -> AppResult<Json<SessionDetail>> {
+    let (messages, meta) = load_session(&state, &caller, &session_id).await?;
+
+    Ok(Json(SessionDetail {
+        active_leaf: branch_path(&messages, meta.active_leaf.as_deref()).last().map(|m| m.id.clone()),
+        branch_count: leaves(&messages).len(),
+        summary: summarize_session(&session_id, &messages, &meta),
+    }))
+}
+
+pub async fn update_session(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    WithRejection(Json(update), _): WithRejection<Json<SessionUpdate>, AppError>,
+) -> AppResult<Json<SessionSummary>> {
+    check_session_id(&session_id)?;
+    let _lock = lock_session(&session_id).await;
+    let (messages, mut meta) = load_session(&state, &caller, &session_id).await?;
+
+    if let Some(title) = update.title {
+        meta.title = title.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    }
+    if let Some(persona) = update.persona {
+        if let Some(id) = &persona {
+            state.config.persona(id)?;
+        }
+        meta.persona = persona;
+    }
+
+    save_meta(&state.config.storage.sessions_dir, &session_id, &meta).await?;
+
+    Ok(Json(summarize_session(&session_id, &messages, &meta)))
+}
+
+// -----------------------
+// POST /api/sessions/{id}/title
+// ตั้งชื่อใหม่จาก branch ที่ใช้อยู่ (ทับชื่อเดิม รวมถึงชื่อที่ผู้ใช้ตั้งเอง)
+// -----------------------
+pub async fn regenerate_title(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+) -> AppResult<Json<SessionSummary>> {
+    let (messages, meta) = load_session(&state, &caller, &session_id).await?;
+
+    let branch = branch_path(&messages, meta.active_leaf.as_deref());
+    let title = generate_title(&state, &caller.user_id, &branch)
+        .instrument(info_span!("sessions.title"))
+        .await?;
+
+    // [ระหว่างรอโมเดล turn อื่นอาจเปลี่ยน metadata แล้ว อ่านใหม่ภายใต้ lock]
+    let _lock = lock_session(&session_id).await;
+    set_title(&state.config.storage.sessions_dir, &session_id, &title).await?;
+    let meta = load_meta(&state.config.storage.sessions_dir, &session_id).await?;
+
+    Ok(Json(summarize_session(&session_id, &messages, &meta)))
+}
+
+// -----------------------
+// DELETE /api/sessions/{id}
+// ลบ chat log + รูปที่แนบ + metadata + ทุก point ใน Qdrant (ข้อความและ summary) + full-text index
+// -----------------------
+pub async fn delete_session(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+) -> AppResult<StatusCode> {
+    check_session_id(&session_id)?;
+    let _lock = lock_session(&session_id).await;
+    let (messages, _) = load_session(&state, &caller, &session_id).await?;
+    let storage = &state.config.storage;
+
+    // [ลบ Qdrant ก่อน ถ้าล้มจะได้ลองใหม่ได้เพราะไฟล์ยังอยู่]
+    delete_session_points(&state.qdrant_client, &state.config.qdrant.collection, &session_id).await?;
+    state.search.delete_session(&session_id).await?;
+
+    // [ลบเฉพาะไฟล์ใน images_dir ของเรา ไม่แตะ URL ภายนอก]
+    let images_prefix = format!("{}/", storage.images_dir);
+    for attachment in messages.iter().flat_map(|m| &m.attachments) {
+        if !attachment.starts_with(&images_prefix) || attachment.contains("..") {
+            continue;
+        }
+        if let Err(e) = fs::remove_file(attachment).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(path = %attachment, error = %e, "Failed to delete image");
+            }
+        }
+    }
+
+    delete_meta(&storage.sessions_dir, &session_id).await?;
+    fs::remove_file(format!("{}/{}.json", storage.chat_logs_dir, session_id)).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// -----------------------
+// GET /api/sessions/{id}/messages?before=&limit=
+// ประวัติของ branch ที่ใช้อยู่ แบ่งหน้าย้อนหลังด้วย cursor = id ของข้อความ
+// -----------------------
+pub async fn list_messages(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    WithRejection(Query(query), _): WithRejection<Query<HistoryQuery>, AppError>,
+) -> AppResult<Json<HistoryPage>> {
+    let (messages, meta) = load_session(&state, &caller, &session_id).await?;
+    let branch = branch_path(&messages, meta.active_leaf.as_deref());
+
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT).clamp(1, MAX_HISTORY_LIMIT);
+    let end = match &query.before {
+        Some(before) => branch.iter()
+            .position(|m| &m.id == before)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown cursor '{before}'")))?,
+        None => branch.len(),
+    };
+    let start = end.saturating_sub(limit);
+
+    let images_dir = &state.config.storage.images_dir;
+    let page = branch[start..end].iter()
+        .map(|m| HistoryMessage {
+            id: m.id.clone(),
+            parent_id: m.parent_id.clone(),
+            role: m.role.clone(),
+            content: m.content.clone(),
+            timestamp: m.timestamp,
+            persona: m.persona.clone(),
+            attachments: m.attachments.iter()
+                .map(|a| Attachment { url: attachment_url(a, images_dir) })
+                .collect(),
+            swipes: m.swipes.clone(),
+            feedback: m.feedback.clone(),
+        })
+        .collect();
+
+    Ok(Json(HistoryPage {
+        messages: page,
+        next_before: (start > 0).then(|| branch[start].id.clone()),
+    }))
+}
+
+// -----------------------
+// GET /api/sessions/{id}/export?format=md|html|json|jsonl
+// md/html/jsonl = branch ที่ใช้อยู่ ส่งทีละข้อความ, json = ทั้ง log ทุก branch (import กลับได้)
+// -----------------------
+pub async fn export_session(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    headers: HeaderMap,
+    WithRejection(Query(query), _): WithRejection<Query<ExportQuery>, AppError>,
+) -> AppResult<Response> {
+    let (messages, meta) = load_session(&state, &caller, &session_id).await?;
+    let format = query.format.unwrap_or(ExportFormat::Markdown);
+    let exported_at = Utc::now();
+
+    let download_headers = [
+        (header::CONTENT_TYPE, format.content_type().to_string()),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", export_filename(&session_id, format)),
+        ),
+    ];
+
+    if format == ExportFormat::Json {
+        let export = SessionExport {
+            version: EXPORT_VERSION,
+            session_id: session_id.clone(),
+            title: meta.title,
+            persona: meta.persona,
+            active_leaf: meta.active_leaf,
+            exported_at,
+            messages,
+        };
+        return Ok((download_headers, serde_json::to_string_pretty(&export)?).into_response());
+    }
+
+    let branch = branch_path(&messages, meta.active_leaf.as_deref());
+    let title = meta.title.clone().unwrap_or_else(|| session_id.clone());
+    let base_url = public_base_url(&headers);
+    let (tx, rx) = mpsc::channel::<Result<String, Infallible>>(16);
+
+    tokio::spawn(async move {
+        let header = match format {
+            ExportFormat::Markdown => markdown_header(&title, &exported_at),
+            ExportFormat::Html => html_header(&title, &exported_at),
+            _ => String::new(),
+        };
+        if !header.is_empty() && tx.send(Ok(header)).await.is_err() {
+            return;
+        }
+
+        for message in &branch {
+            let chunk = match format {
+                ExportFormat::Jsonl => match serde_json::to_string(message) {
+                    Ok(line) => line + "\n",
+                    Err(e) => {
+                        warn!(error = %e, "Failed to serialize message for export");
+                        continue;
+                    }
+                },
+                _ => {
+                    let label = role_label(&state.config, message, meta.persona.as_deref());
+                    let images = export_images(&state, format, &base_url, &message.attachments).await;
+                    if format == ExportFormat::Html {
+                        html_message(&label, message, &images)
+                    } else {
+                        markdown_message(&label, message, &images)
+                    }
+                }
+            };
+
+            // [client ปิดการดาวน์โหลดกลางทาง]
+            if tx.send(Ok(chunk)).await.is_err() {
+                return;
+            }
+        }
+
+        if format == ExportFormat::Html {
+            let _ = tx.send(Ok(html_footer().to_string())).await;
+        }
+    }.instrument(info_span!("sessions.export", format = format.extension())));
+
+    Ok((download_headers, Body::from_stream(ReceiverStream::new(rx))).into_response())
+}
+
+// [html ฝังรูปเป็น data URL ให้เปิด/พิมพ์ได้แบบไม่ต้องต่อ server, md ใช้ลิงก์เต็ม]
+async fn export_images(state: &AppState, format: ExportFormat, base_url: &str, attachments: &[String]) -> Vec<String> {
+    let images_dir = &state.config.storage.images_dir;
+    let mut images = Vec::with_capacity(attachments.len());
+
+    for attachment in attachments {
+        let url = attachment_url(attachment, images_dir);
+        if !url.starts_with('/') {
+            images.push(url);
+            continue;
+        }
+
+        if format == ExportFormat::Html {
+            match encode_image_to_base64(attachment).await {
+                Ok(data_url) => {
+                    images.push(data_url);
+                    continue;
+                }
+                Err(e) => warn!(path = %attachment, error = %e, "Failed to embed image in export"),
+            }
+        }
+
+        images.push(format!("{base_url}{url}"));
+    }
+
+    images
+}
+
+// [อยู่หลัง proxy ให้เชื่อ X-Forwarded-Proto, ไม่มี Host = ใช้ลิงก์แบบ relative]
+fn public_base_url(headers: &HeaderMap) -> String {
+    let Some(host) = headers.get(header::HOST).and_then(|v| v.to_str().ok()) else {
+        return String::new();
+    };
+    let scheme = headers.get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("http");
+
+    format!("{scheme}://{host}")
+}
+
+// -----------------------
+// GET /api/sessions/{id}/memory/search?q=&limit=&scope=branch|session
+// ค้น Qdrant แบบเดียวกับตอนเตรียม prompt ใช้ดูว่าทำไมราพีถึง "จำ" เรื่องนั้นได้
+// -----------------------
+pub async fn search_session_memory(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    WithRejection(Query(query), _): WithRejection<Query<MemorySearchQuery>, AppError>,
+) -> AppResult<Json<MemorySearchResponse>> {
+    let text = query.q.trim().to_string();
+    if text.is_empty() {
+        return Err(AppError::BadRequest("q must not be empty".into()));
+    }
+
+    let (messages, meta) = load_session(&state, &caller, &session_id).await?;
+    let branch_ids = message_ids(&branch_path(&messages, meta.active_leaf.as_deref()));
+    let logged_ids = message_ids(&messages);
+    let limit = query.limit.unwrap_or(state.config.limits.search_limit).clamp(1, MAX_MEMORY_SEARCH_LIMIT);
+
+    let embedding = create_embedding(&state, &caller.user_id, &text)
+        .instrument(info_span!("memory_search.embedding"))
+        .await?;
+
+    let hits = search_memory(
+        &state.qdrant_client,
+        &state.config.qdrant,
+        &session_id,
+        (query.scope == MemoryScope::Branch).then_some(BranchScope { branch_ids: &branch_ids, logged_ids: &logged_ids }),
+        &text,
+        embedding,
+        limit,
+    )
+    .instrument(info_span!("memory_search.qdrant"))
+    .await?;
+
+    let hits = hits.into_iter()
+        .map(|hit| MemorySearchHit {
+            kind: if hit.message.role == "summary" { MemoryKind::Summary } else { MemoryKind::Message },
+            message_id: hit.message.id,
+            score: hit.score,
+            content: hit.message.content,
+            role: hit.message.role,
+            timestamp: hit.message.timestamp,
+        })
+        .collect();
+
+    Ok(Json(MemorySearchResponse { query: text, scope: query.scope, hits }))
+}
+
+// -----------------------
+// PUT /api/sessions/{id}/messages/{message_id}/feedback
+// ให้คะแนนคำตอบ ส่งซ้ำ = แก้คะแนนเดิม
+// -----------------------
+pub async fn set_feedback(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path((session_id, message_id)): Path<(String, String)>,
+    WithRejection(Json(body), _): WithRejection<Json<FeedbackRequest>, AppError>,
+) -> AppResult<Json<Feedback>> {
+    let comment = body.comment.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+    if comment.as_ref().is_some_and(|c| c.chars().count() > MAX_FEEDBACK_COMMENT_CHARS) {
+        return Err(AppError::BadRequest(format!(
+            "comment must be at most {MAX_FEEDBACK_COMMENT_CHARS} characters"
+        )));
+    }
+
+    let feedback = Feedback { rating: body.rating, comment, timestamp: Utc::now() };
+    update_feedback(&state, &caller, &session_id, &message_id, Some(feedback.clone())).await?;
+
+    Ok(Json(feedback))
+}
+
+// -----------------------
+// DELETE /api/sessions/{id}/messages/{message_id}/feedback
+// -----------------------
+pub async fn delete_feedback(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path((session_id, message_id)): Path<(String, String)>,
+) -> AppResult<StatusCode> {
+    update_feedback(&state, &caller, &session_id, &message_id, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn update_feedback(
+    state: &AppState,
+    caller: &Caller,
+    session_id: &str,
+    message_id: &str,
+    feedback: Option<Feedback>,
+) -> AppResult<()> {
+    check_session_id(session_id)?;
+    let _lock = lock_session(session_id).await;
+    let (mut messages, _) = load_session(state, caller, session_id).await?;
+
+    let message = messages.iter_mut()
+        .find(|m| m.id == message_id)
+        .ok_or_else(|| AppError::NotFound(format!("Message '{message_id}' not found")))?;
+    if message.role != "assistant" {
+        return Err(AppError::BadRequest("Only assistant replies can be rated".into()));
+    }
+
+    message.feedback = feedback;
+    write_messages(&state.config.storage.chat_logs_dir, session_id, &messages).await
+}
+
+// -----------------------
+// POST /api/sessions/import?format=auto|rapi|sillytavern|chatgpt
+// body = ไฟล์ดิบ, บันทึก log + metadata ทันที ส่วน embedding ทำเป็น batch เบื้องหลัง
+// -----------------------
+pub async fn import_sessions(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    WithRejection(Query(query), _): WithRejection<Query<ImportQuery>, AppError>,
+    WithRejection(body, _): WithRejection<Bytes, AppError>,
+) -> AppResult<(StatusCode, Json<ImportResponse>)> {
+    let storage = &state.config.storage;
+    let body = std::str::from_utf8(&body)
+        .map_err(|_| AppError::BadRequest("Import file must be UTF-8".into()))?;
+
+    if let Some(persona) = &query.persona {
+        state.config.persona(persona)?;
+    }
+    if let Some(session_id) = &query.session_id {
+        check_session_id(session_id)?;
+    }
+
+    let parsed = parse_import(body, query.format)?;
+    if query.session_id.is_some() && parsed.sessions.len() != 1 {
+        return Err(AppError::BadRequest(format!(
+            "session_id can only be set when importing one session (file has {})", parsed.sessions.len()
+        )));
+    }
+
+    // [embedding ของข้อความที่ import นับเข้าโควต้าเหมือนแชท เต็มแล้วไม่รับตั้งแต่แรก]
+    state.quota.check(&caller.user_id).map_err(QuotaBreach::into_error)?;
+
+    let mut imported = Vec::with_capacity(parsed.sessions.len());
+
+    for session in parsed.sessions {
+        // [ใช้ id เดิมจาก export ของเราถ้ายังไม่มี session นั้น ไม่งั้นออก id ใหม่]
+        let session_id = match (&query.session_id, session.source_id) {
+            (Some(id), _) if session_log_exists(storage, id) => {
+                return Err(AppError::BadRequest(format!("Session '{id}' already exists")));
+            }
+            (Some(id), _) => id.clone(),
+            (None, Some(id)) if check_session_id(&id).is_ok() && !session_log_exists(storage, &id) => id,
+            _ => new_message_id(),
+        };
+
+        let messages: Vec<ChatMessage> = session.messages
+            .into_iter()
+            .map(|mut message| {
+                message.session_id = session_id.clone();
+                // [เก็บเฉพาะ URL: path ใน images_dir อาจเป็นรูปของ session อื่น (ลบ session ที่ import จะลบรูปนั้นไปด้วย)]
+                message.attachments.retain(|a| a.starts_with("http://") || a.starts_with("https://"));
+                if message.role == "assistant" && message.persona.is_none() {
+                    message.persona = query.persona.clone();
+                }
+                message
+            })
+            .collect();
+
+        let meta = SessionMeta {
+            active_leaf: session.active_leaf,
+            title: session.title,
+            // [persona ที่ config นี้ไม่รู้จักจะทำให้แชทต่อไม่ได้]
+            persona: session.persona
+                .filter(|p| state.config.personas.contains_key(p))
+                .or_else(|| query.persona.clone()),
+            owner: Some(caller.user_id.clone()),
+        };
+
+        {
+            let _lock = lock_session(&session_id).await;
+            write_messages(&storage.chat_logs_dir, &session_id, &messages).await?;
+            save_meta(&storage.sessions_dir, &session_id, &meta).await?;
+        }
+        state.search.index(&caller.user_id, messages.clone()).await?;
+
+        imported.push((session_id, meta.title, messages));
+    }
+
+    let sessions = imported.iter()
+        .map(|(session_id, title, messages)| ImportedSessionSummary {
+            session_id: session_id.clone(),
+            title: title.clone(),
+            message_count: messages.len(),
+        })
+        .collect();
+
+    let messages: Vec<ChatMessage> = imported.into_iter().flat_map(|(_, _, messages)| messages).collect();
+    tracing::info!(messages = messages.len(), skipped = parsed.skipped.len(), "Imported sessions");
+    embed_imported(state.clone(), caller.user_id.clone(), messages);
+
+    let skipped_total = parsed.skipped.len();
+    let mut skipped = parsed.skipped;
+    skipped.truncate(MAX_REPORTED_SKIPS);
+
+    Ok((StatusCode::CREATED, Json(ImportResponse { sessions, skipped, skipped_total })))
+}
+
+fn session_log_exists(storage: &StorageConfig, session_id: &str) -> bool {
+    FsPath::new(&format!("{}/{}.json", storage.chat_logs_dir, session_id)).exists()
+}
+
+// [ทีละ IMPORT_EMBED_BATCH ข้อความต่อ 1 request embedding + 1 upsert, batch ที่ล้มข้ามไปแต่ยังทำ batch ถัดไป]
+fn embed_imported(state: Arc<AppState>, user_id: String, messages: Vec<ChatMessage>) {
+    tokio::spawn(async move {
+        let mut ok = true;
+
+        for batch in messages.chunks(IMPORT_EMBED_BATCH) {
+            let texts: Vec<&str> = batch.iter().map(|m| m.content.as_str()).collect();
+
+            let embeddings = match create_embeddings(&state, &user_id, &texts).await {
+                Ok(embeddings) => embeddings,
+                Err(e) => {
+                    warn!(error = %e, batch = batch.len(), "Failed to embed imported messages");
+                    ok = false;
+                    continue;
+                }
+            };
+
+            if let Err(e) = store_messages_to_qdrant(
+                &state.qdrant_client,
+                &state.config.qdrant,
+                batch.iter().zip(embeddings).collect(),
+            ).await {
+                warn!(error = %e, batch = batch.len(), "Failed to store imported embeddings");
+                ok = false;
+            }
+        }
+
+        record_background_job("session_import", ok);
+    }.instrument(info_span!("sessions.import_embed")));
+}
+
+// -----------------------
+// POST /api/sessions/{id}/regenerate
+// ตอบ turn ล่าสุดใหม่ด้วย prompt เดิม แล้วเก็บคำตอบเก่าไว้เป็น swipe
+// -----------------------
+#[tracing::instrument(name = "regenerate", skip_all, fields(session_id = %session_id))]
+pub async fn regenerate(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    WithRejection(body, _): WithRejection<Option<Json<RegenerateRequest>>, AppError>,
+) -> AppResult<Json<RegenerateResponse>> {
+    check_session_id(&session_id)?;
+    let body = body.map(|Json(b)| b).unwrap_or_default();
+    let dir = &state.config.storage.chat_logs_dir;
+
+    // [ทำงานบน branch ที่ใช้อยู่ แต่แก้ข้อความในไฟล์เต็มด้วย id]
+    let (history, meta) = load_session(&state, &caller, &session_id).await?;
+    let branch = branch_path(&history, meta.active_leaf.as_deref());
+
+    let old_reply = branch.last()
+        .filter(|m| m.role == "assistant")
+        .cloned()
+        .ok_or_else(|| AppError::BadRequest("The last message is not a reply".into()))?;
+    let user_idx = branch.iter()
+        .rposition(|m| m.role == "user")
+        .ok_or_else(|| AppError::BadRequest("No user message to answer".into()))?;
+    let user_message = branch[user_idx].clone();
+
+    let input = ChatInput {
+        session_id: session_id.clone(),
+        message: user_message.content.clone(),
+        user_id: caller.user_id.clone(),
+        persona: body.persona
+            .or(old_reply.persona.clone())
+            .or_else(|| Some(DEFAULT_PERSONA.to_string())),
+        images: user_message.attachments.iter().map(|a| ChatImage::from_attachment(a)).collect(),
+        params: body.params,
+    };
+
+    let prior = branch[..user_idx].to_vec();
+    let mut turn = match prepare_turn_with_history(&state, input, prior, &message_ids(&history)).await? {
+        PreparedTurn::Ready(turn) => *turn,
+        PreparedTurn::OffDuty => {
+            return Ok(Json(RegenerateResponse {
+                response: ChatResponse { reply: off_duty_reply(), meta: None },
+                swipes: old_reply.swipes,
+            }));
+        }
+    };
+    turn.user_message_id = user_message.id.clone();
+    turn.reply_id = old_reply.id.clone();
+
+    let completion = complete_with_fallback(&state, turn.task, &turn.route, &turn.messages).await?;
+
+    // [ระหว่างรอโมเดล turn/feedback อื่นอาจแก้ log แล้ว อ่านใหม่ภายใต้ lock แล้วหาคำตอบเดิมด้วย id]
+    let _lock = lock_session(&session_id).await;
+    let mut history = load_full_messages(dir, &session_id).await?;
+    let reply_idx = history.iter()
+        .position(|m| m.id == old_reply.id)
+        .ok_or_else(|| AppError::NotFound(format!("Message '{}' not found", old_reply.id)))?;
+
+    // [ย้ายคำตอบเดิมไปเป็น swipe แล้วใส่คำตอบใหม่แทนที่ (id และตำแหน่งเดิม) feedback ติดไปกับคำตอบเดิม]
+    let reply = &mut history[reply_idx];
+    reply.swipes.push(Swipe {
+        content: std::mem::take(&mut reply.content),
+        timestamp: reply.timestamp,
+        persona: reply.persona.take(),
+        prompt_version: reply.prompt_version.take(),
+        feedback: reply.feedback.take(),
+    });
+    reply.content = completion.reply.clone();
+    reply.timestamp = Utc::now();
+    reply.prompt_version = state.config.personas.get(&turn.persona_id).map(|p| p.prompt_version());
+    reply.persona = Some(turn.persona_id.clone());
+    let swipes = reply.swipes.clone();
+
+    write_messages(dir, &session_id, &history).await?;
+
+    // -----------------
+    // BACKGROUND JOB
+    // quota + แทนที่ vector ของคำตอบเดิมใน Qdrant (point id เดียวกัน) และใน full-text index
+    // -----------------
+    {
+        let state = state.clone();
+        let user_id = turn.user_id.clone();
+        let reply_bg = history[reply_idx].clone();
+        let owner = meta.owner().to_string();
+        let usage = completion.usage;
+
+        tokio::spawn(async move {
+            let mut ok = true;
+
+            if let Some(usage) = usage {
+                state.quota.record(&user_id, usage.prompt_tokens, usage.completion_tokens);
+                if let Err(e) = state.quota.persist().await {
+                    warn!(error = %e, "Failed to persist quota usage");
+                    ok = false;
+                }
+            }
+
+            if let Err(e) = state.search.index(&owner, vec![reply_bg.clone()]).await {
+                warn!(error = %e, "Failed to reindex regenerated reply");
+                ok = false;
+            }
+
+            match create_embedding(&state, &user_id, &reply_bg.content).await {
+                Ok(embedding) => {
+                    if let Err(e) = store_message_to_qdrant(
+                        &state.qdrant_client,
+                        &state.config.qdrant,
+                        &reply_bg,
+                        embedding,
+                    ).await {
+                        warn!(error = %e, "Failed to replace assistant embedding");
+                        ok = false;
+                    }
+                }
+                Err(e) => {
+                    warn!(error = %e, "Failed to embed regenerated reply");
+                    ok = false;
+                }
+            }
+
+            record_background_job("regenerate_persist", ok);
+        }.instrument(info_span!("regenerate.background_job")));
+    }
+
+    Ok(Json(RegenerateResponse {
+        response: ChatResponse {
+            reply: completion.reply,
+            meta: Some(ReplyMeta {
+                persona: turn.persona_id,
+                provider: completion.provider,
+                model: completion.model,
+                message_id: turn.reply_id,
+            }),
+        },
+        swipes,
+    }))
+}
+
+// -----------------------
+// POST /api/sessions/{id}/messages/{message_id}/edit
+// แก้ข้อความ user เดิม = สร้าง branch ใหม่จาก parent ของข้อความนั้น แล้วให้ตอบใหม่
+// -----------------------
+#[tracing::instrument(name = "edit_message", skip_all, fields(session_id = %session_id))]
+pub async fn edit_message(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path((session_id, message_id)): Path<(String, String)>,
+    WithRejection(Json(body), _): WithRejection<Json<EditRequest>, AppError>,
+) -> AppResult<Json<ChatResponse>> {
+    let (messages, _) = load_session(&state, &caller, &session_id).await?;
+
+    let original = messages.iter()
+        .find(|m| m.id == message_id)
+        .ok_or_else(|| AppError::NotFound(format!("Message '{message_id}' not found")))?;
+    if original.role != "user" {
+        return Err(AppError::BadRequest("Only user messages can be edited".into()));
+    }
+
+    let history = match &original.parent_id {
+        Some(parent_id) => branch_path(&messages, Some(parent_id)),
+        None => Vec::new(),
+    };
+
+    // [persona เดิมของคำตอบข้อความนี้]
+    let persona = body.persona.or_else(|| {
+        messages.iter()
+            .find(|m| m.parent_id.as_deref() == Some(message_id.as_str()))
+            .and_then(|m| m.persona.clone())
+    });
+
+    let input = ChatInput {
+        session_id: session_id.clone(),
+        message: body.message,
+        user_id: caller.user_id.clone(),
+        persona,
+        images: original.attachments.iter().map(|a| ChatImage::from_attachment(a)).collect(),
+        params: body.params,
+    };
+    input.validate(state.config.limits.max_images)?;
+
+    let turn = match prepare_turn_with_history(&state, input, history, &message_ids(&messages)).await? {
+        PreparedTurn::Ready(turn) => *turn,
+        PreparedTurn::OffDuty => return Ok(Json(ChatResponse { reply: off_duty_reply(), meta: None })),
+    };
+
+    Ok(Json(reply_to_turn(&state, turn).await?))
+}
+
+pub async fn list_branches(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+) -> AppResult<Json<BranchesResponse>> {
+    let (messages, meta) = load_session(&state, &caller, &session_id).await?;
+    let active_leaf = branch_path(&messages, meta.active_leaf.as_deref()).last().map(|m| m.id.clone());
+
+    let mut branches: Vec<BranchSummary> = leaves(&messages).into_iter()
+        .map(|leaf| BranchSummary {
+            leaf_id: leaf.id.clone(),
+            length: branch_path(&messages, Some(&leaf.id)).len(),
+            updated_at: leaf.timestamp,
+            preview: leaf.content.chars().take(80).collect(),
+            active: active_leaf.as_deref() == Some(leaf.id.as_str()),
+        })
+        .collect();
+    branches.sort_by_key(|b| std::cmp::Reverse(b.updated_at));
+
+    Ok(Json(BranchesResponse { active_leaf, branches }))
+}
+
+pub async fn switch_branch(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Path(session_id): Path<String>,
+    WithRejection(Json(body), _): WithRejection<Json<SwitchBranchRequest>, AppError>,
+) -> AppResult<Json<BranchResponse>> {
+    check_session_id(&session_id)?;
+    let _lock = lock_session(&session_id).await;
+    let (messages, _) = load_session(&state, &caller, &session_id).await?;
+
+    let leaf_id = resolve_leaf(&messages, &body.message_id)
+        .ok_or_else(|| AppError::NotFound(format!("Message '{}' not found", body.message_id)))?;
+
+    set_active_leaf(&state.config.storage.sessions_dir, &session_id, &leaf_id).await?;
+
+    Ok(Json(BranchResponse {
+        messages: branch_path(&messages, Some(&leaf_id)),
+        active_leaf: leaf_id,
+    }))
+}
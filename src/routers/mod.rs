@@ -1,20 +1,58 @@
-use axum::{Router, http::{Method, header, HeaderValue}};
-use tower_http::cors::CorsLayer;
+use axum::extract::DefaultBodyLimit;
+use axum::Router;
 use std::sync::Arc;
+use crate::app::result::AppResult;
 use crate::app::state::AppState;
-use axum::routing::{post};
-use crate::controllers::chat;
+use axum::routing::{get, post, put};
+use crate::controllers::{admin, chat, health, images, metrics, openai_compat, search, sessions};
+use crate::utils::cors::{cors_layer, origin_rules};
+use crate::utils::metrics::track_http;
+use crate::utils::telemetry::{propagate_request_id_layer, scope_request_id, set_request_id_layer, trace_layer};
 
-pub fn api(state: Arc<AppState>) -> Router {
-    let cors = CorsLayer::new()
-        // .allow_origin(HeaderValue::from_static("http://localhost:3000"))
-        .allow_origin(HeaderValue::from_static("https://znnaichat.netlify.app"))
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS])
-        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE, header::ACCEPT])
-        .allow_credentials(true);
+pub fn api(state: Arc<AppState>) -> AppResult<Router> {
+    let cors = cors_layer(origin_rules(&state.config.cors, &state.config.server.env)?);
 
-    Router::<Arc<AppState>>::new()
+    Ok(Router::<Arc<AppState>>::new()
         .route("/api/chat", post(chat::chat))
+        .route("/api/admin/quotas", get(admin::get_quotas).put(admin::update_quotas))
+        .route("/api/admin/quotas/{user_id}", get(admin::get_user_usage))
+        .route("/api/admin/feedback", get(admin::get_feedback_stats))
+        .route("/api/admin/search/reindex", post(admin::reindex_search))
+        .route("/api/admin/datasets/fine-tune", get(admin::export_fine_tune))
+        .route("/api/sessions", get(sessions::list_sessions))
+        .route(
+            "/api/sessions/import",
+            post(sessions::import_sessions).layer(DefaultBodyLimit::max(state.config.limits.import_max_bytes)),
+        )
+        .route(
+            "/api/sessions/{id}",
+            get(sessions::get_session).patch(sessions::update_session).delete(sessions::delete_session),
+        )
+        .route("/api/sessions/{id}/title", post(sessions::regenerate_title))
+        .route("/api/sessions/{id}/export", get(sessions::export_session))
+        .route("/api/sessions/{id}/messages", get(sessions::list_messages))
+        .route("/api/sessions/{id}/memory/search", get(sessions::search_session_memory))
+        .route("/api/sessions/{id}/regenerate", post(sessions::regenerate))
+        .route("/api/sessions/{id}/messages/{message_id}/edit", post(sessions::edit_message))
+        .route(
+            "/api/sessions/{id}/messages/{message_id}/feedback",
+            put(sessions::set_feedback).delete(sessions::delete_feedback),
+        )
+        .route("/api/sessions/{id}/branches", get(sessions::list_branches))
+        .route("/api/sessions/{id}/branch", put(sessions::switch_branch))
+        .route("/api/search", get(search::search))
+        .route("/api/images/{filename}", get(images::get_image))
+        .route("/v1/chat/completions", post(openai_compat::chat_completions))
+        .route("/v1/models", get(openai_compat::list_models))
+        .route("/metrics", get(metrics::metrics))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route_layer(axum::middleware::from_fn(track_http))
         .layer(cors)
-        .with_state(state)
-} 
\ No newline at end of file
+        .layer(axum::middleware::from_fn(scope_request_id))
+        // [layer ล่างสุดทำงานก่อน: ใส่ X-Request-Id -> trace -> ส่ง id กลับใน response]
+        .layer(propagate_request_id_layer())
+        .layer(trace_layer())
+        .layer(set_request_id_layer())
+        .with_state(state))
+}
\ No newline at end of file
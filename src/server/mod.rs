@@ -1,96 +1,145 @@
-use std::{env, net::SocketAddr, sync::Arc, time::Duration};
+use std::{collections::HashMap, net::SocketAddr, sync::{Arc, Mutex}, time::Duration};
 use tokio::signal;
+use tracing::{info, warn};
 use qdrant_client::Qdrant;
 
+use crate::app::config::Config;
 use crate::app::error::AppError;
 use crate::app::result::AppResult;
 use crate::app::state::AppState;
 use crate::routers::api;
 use crate::utils::image::ensure_dir_once;
+use crate::utils::model_router::ModelRouter;
+use crate::utils::openai::OpenAiClient;
 use crate::utils::qdrant::ensure_collection;
+use crate::utils::search_index::{reindex_all, SearchIndex};
+use crate::utils::quota::QuotaManager;
+use crate::utils::telemetry::init_tracing;
 
 pub async fn run() -> AppResult<()> {
-    // โหลด .env ตอน dev เท่านั้น
-    if cfg!(debug_assertions) {
-        dotenv::dotenv()?;
-    }
+    init_tracing();
 
     // -----------------------
-    // Qdrant client (จาก ENV)
+    // Config (config.toml + ENV)
     // -----------------------
-    let qdrant_url = env::var("QDRANT_URL").unwrap_or_else(|_| "http://localhost:6334".into());
-    let qdrant_api_key = env::var("QDRANT_API_KEY").ok();
+    let mut config = Config::load()?;
 
-    let qdrant_client = match qdrant_api_key {
-        Some(k) if !k.is_empty() => {
-            Qdrant::from_url(&qdrant_url)
-                .api_key(k)
+    // -----------------------
+    // Qdrant client
+    // -----------------------
+    let qdrant_client = match &config.qdrant.api_key {
+        Some(k) => {
+            Qdrant::from_url(&config.qdrant.url)
+                .api_key(k.clone())
                 .build()
                 .map_err(|e| AppError::QdrantError(format!("Qdrant connection error: {e}")))?
         }
-        _ => {
-            Qdrant::from_url(&qdrant_url)
+        None => {
+            Qdrant::from_url(&config.qdrant.url)
                 .build()
                 .map_err(|e| AppError::QdrantError(format!("Qdrant connection error: {e}")))?
         }
     };
 
-    ensure_collection(&qdrant_client)
+    config.qdrant.hybrid = ensure_collection(&qdrant_client, &config.qdrant)
         .await
         .map_err(|e| AppError::QdrantError(format!("Failed to create collection: {e}")))?;
 
     // เตรียมโฟลเดอร์สำหรับเก็บรูปอัปโหลด
-    ensure_dir_once("images/chat")?;
+    ensure_dir_once(&config.storage.images_dir)?;
 
     // -----------------------
-    // OpenAI config
+    // Quotas + global budget
+    // ค่าที่แอดมินปรับตอน runtime จะถูกเก็บใน quota_file และทับค่าจาก config
     // -----------------------
-    let openai_key = env::var("OPENAI_API_KEY")?;
-    let openai_model = env::var("OPENAI_MODEL").unwrap_or_else(|_| "gpt-4o".to_string());
+    let quota = Arc::new(QuotaManager::load(
+        config.quota.clone(),
+        &config.storage.quota_file,
+    )?);
 
     // -----------------------
     // Reused HTTP client
-    // หมายเหตุ: ถ้ามีสตรีม ให้ตั้ง timeout แบบ per-request .timeout(None)
+    // stream ใช้อีกตัวที่ไม่มี timeout รวม (timeout ของ reqwest นับถึงอ่าน body จบ คำตอบยาวจะถูกตัดกลางทาง)
+    // ให้ OpenAiClient คุม timeout ถึง response แรก + ช่วงเงียบระหว่าง chunk แทน
     // -----------------------
     let http = reqwest::Client::builder()
-        .connect_timeout(Duration::from_secs(5))
-        .timeout(Duration::from_secs(60))
+        .connect_timeout(Duration::from_secs(config.timeouts.connect_secs))
+        .timeout(Duration::from_secs(config.timeouts.request_secs))
         .pool_max_idle_per_host(10)
         .tcp_keepalive(Some(Duration::from_secs(30)))
         .build()?; // reqwest::Error -> AppError::ReqwestError via `?`
 
+    let stream_http = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(config.timeouts.connect_secs))
+        .pool_max_idle_per_host(10)
+        .tcp_keepalive(Some(Duration::from_secs(30)))
+        .build()?;
+
+    // [client ต่อ provider แต่ละตัวมี circuit breaker ของตัวเอง]
+    let openai = Arc::new(OpenAiClient::new(
+        "openai",
+        http.clone(),
+        stream_http.clone(),
+        &config.openai.base_url,
+        &config.openai.api_key,
+        &config.resilience,
+    ));
+
+    let mut providers = HashMap::from([("openai".to_string(), openai.clone())]);
+    for (name, provider) in &config.providers {
+        providers.insert(name.clone(), Arc::new(OpenAiClient::new(
+            name,
+            http.clone(),
+            stream_http.clone(),
+            &provider.base_url,
+            &provider.api_key,
+            &config.resilience,
+        )));
+    }
+
+    // -----------------------
+    // Router + Server
+    // -----------------------
+    let addr: SocketAddr = format!("{}:{}", config.server.host, config.server.port)
+        .parse()
+        .map_err(|e| AppError::ConfigError(format!("Invalid HOST/PORT: {e}")))?;
+
     // -----------------------
     // Shared AppState
     // -----------------------
+    let router = ModelRouter::new(&config);
+    let search = Arc::new(SearchIndex::open(&config.storage.search_index)?);
     let state = Arc::new(AppState {
+        config,
         qdrant_client,
-        http,
-        openai_key,
-        openai_model,
+        openai,
+        router,
+        providers,
+        quota,
+        search,
+        llm_health: Arc::new(Mutex::new(None)),
     });
-  
-    // -----------------------
-    // Router + Server
-    // -----------------------
-    let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".into());
-    let port: u16 = env::var("PORT")
-        .ok()
-        .and_then(|p| p.parse().ok())
-        .unwrap_or(8080);
 
-    let addr: SocketAddr = format!("{host}:{port}")
-        .parse()
-        .map_err(|e| AppError::BadRequest(format!("Invalid HOST/PORT: {e}")))?;
+    // [index ว่าง (ครั้งแรกหรือถูกลบ) ให้สร้างจาก chat log เบื้องหลัง]
+    if state.search.is_empty().await? {
+        let state = state.clone();
+        tokio::spawn(async move {
+            match reindex_all(&state).await {
+                Ok(sessions) => info!(sessions, "Search index rebuilt"),
+                Err(e) => warn!(error = %e, "Failed to rebuild search index"),
+            }
+        });
+    }
 
-    let app = api(state);
-    println!("App running on http://{addr}");
+    let app = api(state)?;
+    info!(%addr, "App running");
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
 
     axum::serve(listener, app)
         .with_graceful_shutdown(async {
             let _ = signal::ctrl_c().await;
-            eprintln!("Shutting down…");
+            info!("Shutting down…");
         })
         .await?;
 
@@ -0,0 +1,50 @@
+use axum::http::{header, HeaderMap};
+
+use crate::app::auth::{identify, ANONYMOUS_USER};
+use crate::app::config::Config;
+
+fn headers(token: Option<&str>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(token) = token {
+        headers.insert(header::AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+    }
+    headers
+}
+
+fn config(users: &[(&str, &str)]) -> Config {
+    let mut config = Config::default();
+    config.admin.token = Some("admin-token".into());
+    config.auth.users = users.iter().map(|(u, t)| (u.to_string(), t.to_string())).collect();
+    config
+}
+
+#[test]
+fn without_users_every_caller_is_anonymous_except_admin() {
+    let config = config(&[]);
+
+    let caller = identify(&config, &headers(None)).unwrap();
+    assert_eq!(caller.user_id, ANONYMOUS_USER);
+    assert!(caller.owns(None));
+    assert!(!caller.owns(Some("alice")));
+
+    // [client แบบ OpenAI ส่ง API key มาเสมอ]
+    assert_eq!(identify(&config, &headers(Some("sk-whatever"))).unwrap().user_id, ANONYMOUS_USER);
+
+    let admin = identify(&config, &headers(Some("admin-token"))).unwrap();
+    assert!(admin.admin && admin.owns(Some("alice")));
+}
+
+#[test]
+fn with_users_a_known_token_is_required_and_scopes_sessions() {
+    let config = config(&[("alice", "alice-token"), ("bob", "bob-token")]);
+
+    assert!(identify(&config, &headers(None)).is_err());
+    assert!(identify(&config, &headers(Some("nope"))).is_err());
+
+    let alice = identify(&config, &headers(Some("alice-token"))).unwrap();
+    assert_eq!(alice.user_id, "alice");
+    assert!(alice.owns(Some("alice")));
+    assert!(!alice.owns(Some("bob")));
+    // [session เก่าไม่มีเจ้าของ = ของ anonymous]
+    assert!(!alice.owns(None));
+}
@@ -0,0 +1,89 @@
+use std::sync::Mutex;
+
+use crate::app::config::Config;
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+
+// [Config::load อ่าน ENV ของทั้ง process เทสต์ที่แก้ ENV ต้องรันทีละตัว]
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+const BASE: &str = r#"
+[openai]
+api_key = "sk-from-toml"
+model = "gpt-4o"
+"#;
+
+fn load_with(toml: &str, vars: &[(&str, &str)]) -> AppResult<Config> {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = std::env::temp_dir().join(format!("rapi-config-{}.toml", uuid::Uuid::new_v4()));
+    std::fs::write(&path, toml).unwrap();
+
+    std::env::set_var("CONFIG_PATH", &path);
+    for (key, value) in vars {
+        std::env::set_var(key, value);
+    }
+
+    let result = Config::load();
+
+    std::env::remove_var("CONFIG_PATH");
+    for (key, _) in vars {
+        std::env::remove_var(key);
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    result
+}
+
+fn config_error(result: AppResult<Config>) -> String {
+    match result {
+        Err(AppError::ConfigError(message)) => message,
+        other => panic!("expected a config error, got {other:?}"),
+    }
+}
+
+#[test]
+fn env_overrides_toml() {
+    let config = load_with(
+        &format!("{BASE}\n[server]\nport = 8000\n\n[limits]\nsearch_limit = 7\n"),
+        &[("PORT", "9100"), ("OPENAI_MODEL", "gpt-4o-mini"), ("QUOTA_USER_DAILY_TOKENS", "100")],
+    ).unwrap();
+
+    assert_eq!(config.server.port, 9100);
+    assert_eq!(config.openai.model, "gpt-4o-mini");
+    assert_eq!(config.openai.api_key, "sk-from-toml");
+    assert_eq!(config.limits.search_limit, 7);
+    assert_eq!(config.quota.user.daily_tokens, Some(100));
+}
+
+#[test]
+fn invalid_env_value_is_rejected() {
+    let message = config_error(load_with(BASE, &[("PORT", "eighty")]));
+
+    assert!(message.contains("PORT"), "{message}");
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    let message = config_error(load_with(&format!("{BASE}\n[server]\nprot = 8000\n"), &[]));
+    assert!(message.contains("prot"), "{message}");
+
+    let message = config_error(load_with(&format!("{BASE}\n[personas.rapi]\nsystem_promt = \"hi\"\n"), &[]));
+    assert!(message.contains("system_promt"), "{message}");
+}
+
+#[test]
+fn recent_messages_must_not_exceed_summary_threshold() {
+    let message = config_error(load_with(
+        &format!("{BASE}\n[limits]\nsummary_threshold = 10\nrecent_messages = 20\n"),
+        &[],
+    ));
+
+    assert!(message.contains("limits.recent_messages"), "{message}");
+}
+
+#[test]
+fn example_config_is_valid() {
+    let example = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/config.example.toml")).unwrap();
+
+    assert!(load_with(&example, &[("OPENAI_API_KEY", "sk-test")]).is_ok());
+}
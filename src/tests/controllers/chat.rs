@@ -0,0 +1,51 @@
+use crate::controllers::chat::{load_full_messages, persist_interrupted_turn, ChatTurn, MessageRequest};
+use crate::tests::support::test_state;
+use crate::utils::model_router::Task;
+
+fn turn(state: &crate::app::state::AppState, message: &str) -> ChatTurn {
+    ChatTurn {
+        session_id: uuid::Uuid::new_v4().to_string(),
+        user_id: "alice".into(),
+        persona_id: "rapi".into(),
+        message: message.into(),
+        task: Task::Chat,
+        route: state.router.route(Task::Chat).clone(),
+        messages: vec![MessageRequest::text("system", "prompt"), MessageRequest::text("user", message)],
+        attachments: Vec::new(),
+        parent_id: None,
+        user_message_id: "user-1".into(),
+        reply_id: "reply-1".into(),
+        owner: Some("alice".into()),
+        user_embedding: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn failed_stream_charges_estimate_and_keeps_partial_reply() {
+    let test = test_state();
+    let state = test.state.clone();
+    let turn = turn(&state, "สวัสดีค่ะ ราพี");
+
+    persist_interrupted_turn(state.clone(), &turn, "สวัสดีค่ะ ผู้บัญ", None).await.unwrap();
+
+    assert!(state.quota.usage("alice").day_tokens > 0);
+
+    let messages = load_full_messages(&state.config.storage.chat_logs_dir, &turn.session_id).await.unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].content, "สวัสดีค่ะ ราพี");
+    assert!(!messages[0].interrupted);
+    assert_eq!(messages[1].content, "สวัสดีค่ะ ผู้บัญ");
+    assert!(messages[1].interrupted);
+}
+
+#[tokio::test]
+async fn failed_stream_prefers_usage_reported_by_provider() {
+    let test = test_state();
+    let state = test.state.clone();
+    let turn = turn(&state, "hello");
+    let usage = crate::utils::completion::OpenAiUsage { prompt_tokens: 1000, completion_tokens: 7 };
+
+    persist_interrupted_turn(state.clone(), &turn, "", Some(usage)).await.unwrap();
+
+    assert_eq!(state.quota.usage("alice").day_tokens, 1007);
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use qdrant_client::qdrant::condition::ConditionOneOf;
+use qdrant_client::qdrant::point_id::PointIdOptions;
+use qdrant_client::qdrant::r#match::MatchValue;
+use qdrant_client::qdrant::{Condition, Filter};
+
+use crate::tests::support::{get_json, seed_session, test_state_with};
+use crate::utils::qdrant::{memory_filter, BranchScope};
+use crate::utils::quota::QuotaLimits;
+
+// [ประเมิน filter แบบเดียวกับ Qdrant กับ point ปลอม (id + payload string) เฉพาะ condition ที่ memory_filter ใช้]
+fn matches(filter: &Filter, id: &str, payload: &HashMap<&str, &str>) -> bool {
+    filter.must.iter().all(|c| condition(c, id, payload))
+        && (filter.should.is_empty() || filter.should.iter().any(|c| condition(c, id, payload)))
+        && !filter.must_not.iter().any(|c| condition(c, id, payload))
+}
+
+fn condition(condition: &Condition, id: &str, payload: &HashMap<&str, &str>) -> bool {
+    match condition.condition_one_of.as_ref().unwrap() {
+        ConditionOneOf::Filter(filter) => matches(filter, id, payload),
+        ConditionOneOf::HasId(has_id) => has_id.has_id.iter()
+            .any(|p| matches!(&p.point_id_options, Some(PointIdOptions::Uuid(u)) if u == id)),
+        ConditionOneOf::IsEmpty(is_empty) => payload.get(is_empty.key.as_str()).is_none_or(|v| v.is_empty()),
+        ConditionOneOf::Field(field) => {
+            let value = payload.get(field.key.as_str());
+            match field.r#match.as_ref().and_then(|m| m.match_value.as_ref()) {
+                Some(MatchValue::Keyword(k)) => value == Some(&k.as_str()),
+                Some(MatchValue::Keywords(ks)) => value.is_some_and(|v| ks.strings.iter().any(|k| k == v)),
+                other => panic!("unexpected match {other:?}"),
+            }
+        }
+        other => panic!("unexpected condition {other:?}"),
+    }
+}
+
+fn point<'a>(session_id: &'a str, role: &'a str, parent_id: &'a str) -> HashMap<&'a str, &'a str> {
+    HashMap::from([("session_id", session_id), ("role", role), ("parent_id", parent_id)])
+}
+
+#[test]
+fn branch_scope_keeps_branch_messages_summaries_and_legacy_points() {
+    let branch_ids = vec!["a".to_string(), "b".to_string()];
+    let logged_ids = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+    let filter = memory_filter("s1", Some(BranchScope { branch_ids: &branch_ids, logged_ids: &logged_ids }));
+
+    assert!(matches(&filter, "b", &point("s1", "assistant", "a")));
+    assert!(matches(&filter, "sum-1", &point("s1", "summary", "b")));
+    assert!(matches(&filter, "legacy", &point("s1", "user", "")));
+
+    // [ข้อความ/summary ของ branch อื่น และ session อื่น]
+    assert!(!matches(&filter, "c", &point("s1", "assistant", "a")));
+    assert!(!matches(&filter, "c", &point("s1", "user", "")));
+    assert!(!matches(&filter, "sum-2", &point("s1", "summary", "c")));
+    assert!(!matches(&filter, "a", &point("s2", "user", "")));
+}
+
+#[test]
+fn session_scope_only_filters_by_session() {
+    let filter = memory_filter("s1", None);
+
+    assert!(matches(&filter, "c", &point("s1", "assistant", "a")));
+    assert!(matches(&filter, "sum-2", &point("s1", "summary", "c")));
+    assert!(!matches(&filter, "a", &point("s2", "user", "")));
+}
+
+#[tokio::test]
+async fn memory_search_is_scoped_to_the_session_owner() {
+    // [โควต้า 0 = ผ่านการเช็คเจ้าของแล้วจะได้ 429 ตอนจะสร้าง embedding โดยไม่ต้องมี OpenAI/Qdrant จริง]
+    let test = test_state_with(|config| {
+        config.auth.users = HashMap::from([
+            ("alice".to_string(), "alice-token".to_string()),
+            ("bob".to_string(), "bob-token".to_string()),
+        ]);
+        config.admin.token = Some("admin-token".into());
+        config.quota.user = QuotaLimits { daily_tokens: Some(0), ..Default::default() };
+    });
+    let state = &test.state;
+    seed_session(state, "s1", "alice", 2).await;
+    let uri = "/api/sessions/s1/memory/search?q=hello";
+
+    assert_eq!(get_json(state, uri, Some("bob-token")).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(get_json(state, uri, None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(get_json(state, uri, Some("alice-token")).await.0, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(get_json(state, uri, Some("admin-token")).await.0, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        get_json(state, "/api/sessions/missing/memory/search?q=hello", Some("alice-token")).await.0,
+        StatusCode::NOT_FOUND,
+    );
+}
@@ -1 +1,7 @@
-// pub mod file_upload;
\ No newline at end of file
+// pub mod file_upload;
+#[cfg(test)]
+pub mod chat;
+#[cfg(test)]
+pub mod sessions;
+#[cfg(test)]
+pub mod memory_search;
@@ -0,0 +1,69 @@
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::app::auth::ANONYMOUS_USER;
+use crate::tests::support::{get_json, seed_session, test_state};
+use crate::utils::session_meta::set_active_leaf;
+
+fn ids(page: &Value) -> Vec<&str> {
+    page["messages"].as_array().unwrap().iter().map(|m| m["id"].as_str().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn list_messages_pages_backwards_with_before_cursor() {
+    let test = test_state();
+    let state = &test.state;
+    seed_session(state, "s1", ANONYMOUS_USER, 5).await;
+
+    let (status, first) = get_json(state, "/api/sessions/s1/messages?limit=2", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&first), ["m4", "m5"]);
+    assert_eq!(first["next_before"], "m4");
+
+    let (status, middle) = get_json(state, "/api/sessions/s1/messages?limit=2&before=m4", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&middle), ["m2", "m3"]);
+    assert_eq!(middle["next_before"], "m2");
+
+    let (status, last) = get_json(state, "/api/sessions/s1/messages?limit=2&before=m2", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&last), ["m1"]);
+    assert_eq!(last["next_before"], Value::Null);
+}
+
+#[tokio::test]
+async fn list_messages_returns_everything_on_one_page_when_it_fits() {
+    let test = test_state();
+    let state = &test.state;
+    seed_session(state, "s1", ANONYMOUS_USER, 3).await;
+
+    let (status, page) = get_json(state, "/api/sessions/s1/messages", None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&page), ["m1", "m2", "m3"]);
+    assert_eq!(page["next_before"], Value::Null);
+}
+
+#[tokio::test]
+async fn list_messages_rejects_unknown_cursor() {
+    let test = test_state();
+    let state = &test.state;
+    seed_session(state, "s1", ANONYMOUS_USER, 3).await;
+
+    let (status, _) = get_json(state, "/api/sessions/s1/messages?before=nope", None).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn list_messages_follows_the_active_branch() {
+    let test = test_state();
+    let state = &test.state;
+    seed_session(state, "s1", ANONYMOUS_USER, 5).await;
+    set_active_leaf(&state.config.storage.sessions_dir, "s1", "m3").await.unwrap();
+
+    let (_, page) = get_json(state, "/api/sessions/s1/messages?limit=2", None).await;
+
+    assert_eq!(ids(&page), ["m2", "m3"]);
+    assert_eq!(page["next_before"], "m2");
+}
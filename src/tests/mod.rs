@@ -1 +1,9 @@
-pub mod controllers;
\ No newline at end of file
+pub mod controllers;
+#[cfg(test)]
+pub mod app;
+#[cfg(test)]
+pub mod routers;
+#[cfg(test)]
+pub mod utils;
+#[cfg(test)]
+pub mod support;
@@ -0,0 +1,126 @@
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use tower::ServiceExt;
+
+use crate::app::config::{CorsConfig, CorsEnvironment};
+use crate::utils::cors::{cors_layer, origin_rules, OriginRule};
+
+fn config() -> CorsConfig {
+    let mut config = CorsConfig {
+        origins: vec![
+            "https://znnaichat.netlify.app".into(),
+            "https://*.preview.netlify.app".into(),
+            "https://*--znnaichat.netlify.app".into(),
+        ],
+        environments: Default::default(),
+    };
+    config.environments.insert("development".into(), CorsEnvironment {
+        origins: vec!["http://localhost:*".into()],
+    });
+    config
+}
+
+fn app(config: &CorsConfig, app_env: &str) -> Router {
+    Router::new()
+        .route("/api/chat", post(|| async { "ok" }))
+        .layer(cors_layer(origin_rules(config, app_env).unwrap()))
+}
+
+async fn preflight(app_env: &str, origin: &str) -> (StatusCode, Option<String>) {
+    let (status, allow_origin, _) = preflight_with(&config(), app_env, origin).await;
+    (status, allow_origin)
+}
+
+async fn preflight_with(config: &CorsConfig, app_env: &str, origin: &str) -> (StatusCode, Option<String>, Option<String>) {
+    let req = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/api/chat")
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .body(Body::empty())
+        .unwrap();
+
+    let res = app(config, app_env).oneshot(req).await.unwrap();
+    let header = |name| res.headers().get(name).map(|v: &header::HeaderValue| v.to_str().unwrap().to_string());
+    let allow_origin = header(header::ACCESS_CONTROL_ALLOW_ORIGIN);
+    let allow_credentials = header(header::ACCESS_CONTROL_ALLOW_CREDENTIALS);
+
+    (res.status(), allow_origin, allow_credentials)
+}
+
+#[tokio::test]
+async fn preflight_allows_exact_origin() {
+    let (status, allow_origin) = preflight("production", "https://znnaichat.netlify.app").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(allow_origin.as_deref(), Some("https://znnaichat.netlify.app"));
+}
+
+#[tokio::test]
+async fn preflight_allows_wildcard_subdomain() {
+    let origin = "https://deploy-preview-12--rapi.preview.netlify.app";
+    let (_, allow_origin) = preflight("production", origin).await;
+
+    assert_eq!(allow_origin.as_deref(), Some(origin));
+}
+
+#[tokio::test]
+async fn preflight_allows_deploy_preview_pattern() {
+    let origin = "https://deploy-preview-7--znnaichat.netlify.app";
+    let (_, allow_origin) = preflight("production", origin).await;
+
+    assert_eq!(allow_origin.as_deref(), Some(origin));
+}
+
+#[tokio::test]
+async fn preflight_rejects_suffix_without_subdomain() {
+    let (_, allow_origin) = preflight("production", "https://preview.netlify.app").await;
+
+    assert_eq!(allow_origin, None);
+}
+
+#[tokio::test]
+async fn preflight_rejects_unknown_origin() {
+    let (_, allow_origin) = preflight("production", "https://evil.example.com").await;
+
+    assert_eq!(allow_origin, None);
+}
+
+#[tokio::test]
+async fn preflight_rejects_lookalike_origin() {
+    let (_, allow_origin) = preflight("production", "https://znnaichat.netlify.app.evil.com").await;
+
+    assert_eq!(allow_origin, None);
+}
+
+#[tokio::test]
+async fn preflight_uses_environment_origins() {
+    let (_, dev) = preflight("development", "http://localhost:3000").await;
+    let (_, prod) = preflight("production", "http://localhost:3000").await;
+
+    assert_eq!(dev.as_deref(), Some("http://localhost:3000"));
+    assert_eq!(prod, None);
+}
+
+#[tokio::test]
+async fn preflight_credentials_only_for_listed_origins() {
+    let (_, _, credentials) = preflight_with(&config(), "production", "https://znnaichat.netlify.app").await;
+    assert_eq!(credentials.as_deref(), Some("true"));
+
+    let any = CorsConfig { origins: vec!["*".into()], environments: Default::default() };
+    let (_, allow_origin, credentials) = preflight_with(&any, "production", "https://evil.example.com").await;
+    assert_eq!(allow_origin.as_deref(), Some("*"));
+    assert_eq!(credentials, None);
+}
+
+#[test]
+fn parse_rejects_malformed_origins() {
+    assert!(OriginRule::parse("znnaichat.netlify.app").is_err());
+    assert!(OriginRule::parse("https://znnaichat.netlify.app/").is_err());
+    assert!(OriginRule::parse("https://app.*.netlify.app").is_err());
+    assert!(OriginRule::parse("ftp://example.com").is_err());
+    assert!(OriginRule::parse("https://*").is_err());
+    assert!(OriginRule::parse("*").is_ok());
+}
@@ -0,0 +1 @@
+pub mod cors;
\ No newline at end of file
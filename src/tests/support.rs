@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use qdrant_client::Qdrant;
+use tower::ServiceExt;
+
+use crate::app::config::Config;
+use crate::app::state::AppState;
+use crate::controllers::chat::{save_messages, ChatMessage};
+use crate::routers::api;
+use crate::utils::model_router::ModelRouter;
+use crate::utils::openai::OpenAiClient;
+use crate::utils::quota::QuotaManager;
+use crate::utils::search_index::SearchIndex;
+use crate::utils::session_meta::{save_meta, SessionMeta};
+
+// -----------------------
+// AppState สำหรับเทสต์ handler
+// ไฟล์ทั้งหมดอยู่ใน temp dir ของแต่ละเทสต์ ส่วน OpenAI/Qdrant ชี้ไป port ที่ไม่มีใครฟัง
+// (Qdrant client ไม่ต่อจนกว่าจะถูกเรียกใช้ งานเบื้องหลังที่เรียกจะแค่ warn)
+// -----------------------
+pub struct TestState {
+    pub state: Arc<AppState>,
+    dir: PathBuf,
+}
+
+impl Drop for TestState {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+pub fn test_state() -> TestState {
+    test_state_with(|_| {})
+}
+
+pub fn test_state_with(configure: impl FnOnce(&mut Config)) -> TestState {
+    let dir = std::env::temp_dir().join(format!("rapi-test-{}", uuid::Uuid::new_v4()));
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+    let mut config = Config::default();
+    config.openai.base_url = "http://127.0.0.1:9/v1".into();
+    config.openai.api_key = "test".into();
+    config.qdrant.url = "http://127.0.0.1:9".into();
+    config.storage.chat_logs_dir = path("chat_logs");
+    config.storage.images_dir = path("images");
+    config.storage.prompt_logs_dir = path("logs");
+    config.storage.sessions_dir = path("sessions");
+    config.storage.quota_file = path("quota.json");
+    config.storage.search_index = ":memory:".into();
+    configure(&mut config);
+
+    let http = reqwest::Client::new();
+    let openai = Arc::new(OpenAiClient::new(
+        "openai",
+        http.clone(),
+        http,
+        &config.openai.base_url,
+        &config.openai.api_key,
+        &config.resilience,
+    ));
+
+    let state = AppState {
+        qdrant_client: Qdrant::from_url(&config.qdrant.url).skip_compatibility_check().build().unwrap(),
+        providers: HashMap::from([("openai".to_string(), openai.clone())]),
+        openai,
+        router: ModelRouter::new(&config),
+        quota: Arc::new(QuotaManager::load(config.quota.clone(), &config.storage.quota_file).unwrap()),
+        search: Arc::new(SearchIndex::open(&config.storage.search_index).unwrap()),
+        llm_health: Arc::new(Mutex::new(None)),
+        config,
+    };
+
+    TestState { state: Arc::new(state), dir }
+}
+
+// [session เส้นเดียว m1 -> m2 -> ... ของ owner คืน id เรียงจากเก่าไปใหม่]
+pub async fn seed_session(state: &AppState, session_id: &str, owner: &str, count: usize) -> Vec<String> {
+    let mut messages: Vec<ChatMessage> = Vec::new();
+    for i in 1..=count {
+        let role = if i % 2 == 1 { "user" } else { "assistant" };
+        messages.push(ChatMessage {
+            parent_id: messages.last().map(|m| m.id.clone()),
+            ..ChatMessage::new(&format!("m{i}"), session_id, role, &format!("message {i}"))
+        });
+    }
+    let ids = messages.iter().map(|m| m.id.clone()).collect();
+
+    save_messages(&state.config.storage.chat_logs_dir, session_id, messages).await.unwrap();
+    let meta = SessionMeta { owner: Some(owner.to_string()), ..Default::default() };
+    save_meta(&state.config.storage.sessions_dir, session_id, &meta).await.unwrap();
+
+    ids
+}
+
+// [ยิง GET ผ่าน router จริง (auth + extractor ครบ) คืน status + body ที่เป็น JSON]
+pub async fn get_json(state: &Arc<AppState>, uri: &str, token: Option<&str>) -> (StatusCode, serde_json::Value) {
+    let mut req = Request::builder().uri(uri);
+    if let Some(token) = token {
+        req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+
+    let res = api(state.clone()).unwrap()
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = res.status();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+
+    (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+}
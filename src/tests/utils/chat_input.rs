@@ -0,0 +1,151 @@
+use axum::body::Body;
+use axum::extract::{FromRequest, Request};
+use axum::http::header::CONTENT_TYPE;
+use base64::engine::general_purpose;
+use base64::Engine as _;
+use serde_json::json;
+
+use crate::app::error::AppError;
+use crate::tests::support::{test_state, TestState};
+use crate::utils::chat_input::{ChatImage, ChatInput};
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0";
+const BOUNDARY: &str = "rapi-test-boundary";
+
+fn test_state_with_images_dir() -> TestState {
+    let test = test_state();
+    std::fs::create_dir_all(&test.state.config.storage.images_dir).unwrap();
+    test
+}
+
+async fn extract(test: &TestState, content_type: &str, body: Vec<u8>) -> Result<ChatInput, AppError> {
+    let req = Request::builder()
+        .method("POST")
+        .uri("/api/chat")
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .unwrap();
+
+    ChatInput::from_request(req, &test.state).await
+}
+
+async fn extract_json(test: &TestState, body: serde_json::Value) -> Result<ChatInput, AppError> {
+    extract(test, "application/json", body.to_string().into_bytes()).await
+}
+
+// [field = (name, filename ถ้าเป็นไฟล์, data)]
+async fn extract_multipart(test: &TestState, fields: &[(&str, Option<&str>, &[u8])]) -> Result<ChatInput, AppError> {
+    let mut body = Vec::new();
+    for (name, filename, data) in fields {
+        body.extend_from_slice(format!("--{BOUNDARY}\r\n").as_bytes());
+        match filename {
+            Some(filename) => body.extend_from_slice(format!(
+                "Content-Disposition: form-data; name=\"{name}\"; filename=\"{filename}\"\r\nContent-Type: image/png\r\n\r\n"
+            ).as_bytes()),
+            None => body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{name}\"\r\n\r\n").as_bytes()),
+        }
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+
+    extract(test, &format!("multipart/form-data; boundary={BOUNDARY}"), body).await
+}
+
+#[tokio::test]
+async fn json_body_reads_params_images_and_ignores_unknown_keys() {
+    let test = test_state_with_images_dir();
+    let input = extract_json(&test, json!({
+        "session_id": "s1",
+        "message": "สวัสดี",
+        "persona": "",
+        "temperature": 0.4,
+        "stop": ["END"],
+        "images": ["https://example.com/a.png", general_purpose::STANDARD.encode(PNG)],
+        "user_id": "old-client",
+    })).await.unwrap();
+
+    assert_eq!(input.session_id, "s1");
+    assert_eq!(input.message, "สวัสดี");
+    assert_eq!(input.user_id, "anonymous");
+    assert_eq!(input.persona, None);
+    assert_eq!(input.params.temperature, Some(0.4));
+    assert_eq!(input.params.stop, Some(vec!["END".to_string()]));
+    assert!(matches!(&input.images[0], ChatImage::Url(url) if url == "https://example.com/a.png"));
+    assert!(matches!(&input.images[1], ChatImage::Stored(path) if std::path::Path::new(path).exists()));
+}
+
+#[tokio::test]
+async fn json_body_rejects_bad_json_and_bad_values() {
+    let test = test_state();
+
+    let broken = extract(&test, "application/json", b"{\"session_id\": ".to_vec()).await;
+    assert!(matches!(broken, Err(AppError::InvalidJson(_))));
+
+    let wrong_type = extract_json(&test, json!({ "session_id": "s1", "message": "hi", "temperature": "hot" })).await;
+    assert!(matches!(wrong_type, Err(AppError::InvalidJson(_))));
+
+    let not_base64 = extract_json(&test, json!({ "session_id": "s1", "images": ["not base64!"] })).await;
+    assert!(matches!(not_base64, Err(AppError::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn rejects_missing_or_unsafe_session_id() {
+    let test = test_state();
+
+    for session_id in ["", "../etc/passwd", ".hidden", "a/b"] {
+        let result = extract_json(&test, json!({ "session_id": session_id, "message": "hi" })).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))), "{session_id:?} should be rejected");
+    }
+
+    let result = extract_multipart(&test, &[("session_id", None, b"../x"), ("message", None, b"hi")]).await;
+    assert!(matches!(result, Err(AppError::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn multipart_reads_fields_images_and_params() {
+    let test = test_state_with_images_dir();
+    let input = extract_multipart(&test, &[
+        ("session_id", None, b"s1"),
+        ("message", None, "สวัสดี".as_bytes()),
+        ("persona", None, b"rapi"),
+        ("temperature", None, b"0.7"),
+        ("max_tokens", None, b""),
+        ("stop", None, b"A"),
+        ("stop", None, b"B"),
+        ("image", Some("cat.png"), PNG),
+        ("image", Some("empty.png"), b""),
+        ("user_id", None, b"ignored"),
+    ]).await.unwrap();
+
+    assert_eq!(input.session_id, "s1");
+    assert_eq!(input.message, "สวัสดี");
+    assert_eq!(input.persona.as_deref(), Some("rapi"));
+    assert_eq!(input.params.temperature, Some(0.7));
+    assert_eq!(input.params.max_tokens, None);
+    assert_eq!(input.params.stop, Some(vec!["A".to_string(), "B".to_string()]));
+    assert_eq!(input.images.len(), 1);
+    assert!(matches!(&input.images[0], ChatImage::Stored(path) if path.ends_with(".png")));
+}
+
+#[tokio::test]
+async fn multipart_rejects_bad_params_and_non_images() {
+    let test = test_state_with_images_dir();
+
+    let bad_param = extract_multipart(&test, &[("session_id", None, b"s1"), ("temperature", None, b"warm")]).await;
+    assert!(matches!(bad_param, Err(AppError::BadRequest(_))));
+
+    let not_image = extract_multipart(&test, &[("session_id", None, b"s1"), ("image", Some("a.png"), b"plain text")]).await;
+    assert!(matches!(not_image, Err(AppError::BadRequest(_))));
+
+    let empty = extract_multipart(&test, &[("session_id", None, b"s1")]).await;
+    assert!(matches!(empty, Err(AppError::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn rejects_other_content_types() {
+    let test = test_state();
+    let result = extract(&test, "text/plain", b"hi".to_vec()).await;
+
+    assert!(matches!(result, Err(AppError::UnsupportedMediaType(_))));
+}
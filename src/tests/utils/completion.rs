@@ -0,0 +1,69 @@
+use crate::app::config::GenerationLimits;
+use crate::utils::completion::GenerationParams;
+
+fn limits() -> GenerationLimits {
+    GenerationLimits { temperature_max: 1.5, top_p_min: 0.1, max_tokens: 2048, presence_penalty_max: 2.0, max_stop: 2 }
+}
+
+#[test]
+fn merge_prefers_request_over_persona_over_route() {
+    let route = GenerationParams { temperature: Some(0.3), top_p: Some(0.9), max_tokens: Some(400), ..Default::default() };
+    let persona = GenerationParams { temperature: Some(0.8), presence_penalty: Some(0.3), ..Default::default() };
+    let request = GenerationParams { temperature: Some(0.1), stop: Some(vec!["END".into()]), ..Default::default() };
+
+    let merged = route.merge(&persona).merge(&request);
+
+    assert_eq!(merged, GenerationParams {
+        temperature: Some(0.1),
+        top_p: Some(0.9),
+        max_tokens: Some(400),
+        presence_penalty: Some(0.3),
+        seed: None,
+        stop: Some(vec!["END".into()]),
+    });
+}
+
+#[test]
+fn clamp_keeps_values_within_limits() {
+    let clamp = |params: GenerationParams| params.clamp(&limits());
+
+    let low = clamp(GenerationParams {
+        temperature: Some(-1.0),
+        top_p: Some(0.0),
+        max_tokens: Some(0),
+        presence_penalty: Some(-5.0),
+        ..Default::default()
+    });
+    assert_eq!((low.temperature, low.top_p, low.max_tokens, low.presence_penalty), (Some(0.0), Some(0.1), Some(1), Some(-2.0)));
+
+    let high = clamp(GenerationParams {
+        temperature: Some(3.0),
+        top_p: Some(1.5),
+        max_tokens: Some(100_000),
+        presence_penalty: Some(5.0),
+        ..Default::default()
+    });
+    assert_eq!((high.temperature, high.top_p, high.max_tokens, high.presence_penalty), (Some(1.5), Some(1.0), Some(2048), Some(2.0)));
+
+    let within = GenerationParams { temperature: Some(0.7), top_p: Some(0.5), max_tokens: Some(100), presence_penalty: Some(0.0), ..Default::default() };
+    assert_eq!(clamp(within.clone()), within);
+}
+
+#[test]
+fn clamp_drops_empty_stops_and_caps_their_count() {
+    let stop = |values: &[&str]| GenerationParams {
+        stop: Some(values.iter().map(|s| s.to_string()).collect()),
+        ..Default::default()
+    }.clamp(&limits()).stop;
+
+    assert_eq!(stop(&["", "A", "", "B", "C"]), Some(vec!["A".to_string(), "B".to_string()]));
+    assert_eq!(stop(&["", ""]), None);
+}
+
+#[test]
+fn non_finite_values_are_reported() {
+    assert_eq!(GenerationParams { temperature: Some(0.5), ..Default::default() }.non_finite(), None);
+    assert_eq!(GenerationParams { temperature: Some(f32::NAN), ..Default::default() }.non_finite(), Some("temperature"));
+    assert_eq!(GenerationParams { top_p: Some(f32::INFINITY), ..Default::default() }.non_finite(), Some("top_p"));
+    assert_eq!(GenerationParams { presence_penalty: Some(f32::NEG_INFINITY), ..Default::default() }.non_finite(), Some("presence_penalty"));
+}
@@ -0,0 +1,50 @@
+use crate::controllers::chat::ChatMessage;
+use crate::utils::dataset::{fine_tune_example, redact_pii, split_for, Split};
+
+#[test]
+fn redact_pii_masks_contact_and_id_numbers() {
+    let text = "เมล rapi@nikke.gg โทร 081-234-5678 บัตร 1-2345-67890-12-3 การ์ด 4111 1111 1111 1111 ไอพี 10.0.0.12";
+
+    assert_eq!(
+        redact_pii(text),
+        "เมล [EMAIL] โทร [PHONE] บัตร [ID_NUMBER] การ์ด [CARD] ไอพี [IP]"
+    );
+    assert_eq!(redact_pii("ปี 2024 ราคา 1,500 บาท"), "ปี 2024 ราคา 1,500 บาท");
+}
+
+#[test]
+fn redact_pii_masks_phone_shaped_numbers_only() {
+    for phone in ["0812345678", "02 123 4567", "+66 81 234 5678", "+66812345678", "(02) 123-4567", "555-123-4567"] {
+        assert_eq!(redact_pii(&format!("โทร {phone} นะ")), "โทร [PHONE] นะ", "{phone}");
+    }
+
+    let text = "คำสั่งซื้อ 12345678 เลขพัสดุ 4521987 ปี 2024 ยอด 1500000 บาท รหัส 98765432";
+    assert_eq!(redact_pii(text), text);
+}
+
+#[test]
+fn split_is_stable_and_respects_ratio() {
+    assert_eq!(split_for("session-a", 0.0), Split::Train);
+    assert_eq!(split_for("session-a", 0.3), split_for("session-a", 0.3));
+
+    let validation = (0..1000)
+        .filter(|i| split_for(&format!("session-{i}"), 0.2) == Split::Validation)
+        .count();
+    assert!((150..250).contains(&validation), "validation = {validation}");
+}
+
+#[test]
+fn fine_tune_example_drops_unanswered_tail() {
+    let branch = vec![
+        ChatMessage::new("1", "s", "user", "ติดต่อ a@b.co"),
+        ChatMessage::new("2", "s", "assistant", "รับทราบค่ะ"),
+        ChatMessage::new("3", "s", "user", "ยังไม่มีคำตอบ"),
+    ];
+
+    let example = fine_tune_example("system", &branch, true).unwrap();
+    let roles: Vec<&str> = example.messages.iter().map(|m| m.role.as_str()).collect();
+
+    assert_eq!(roles, ["system", "user", "assistant"]);
+    assert_eq!(example.messages[1].content, "ติดต่อ [EMAIL]");
+    assert!(fine_tune_example("system", &branch[..1], true).is_none());
+}
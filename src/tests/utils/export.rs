@@ -0,0 +1,23 @@
+use crate::controllers::chat::ChatMessage;
+use crate::utils::export::{escape_html, export_filename, html_message, markdown_message, ExportFormat};
+
+#[test]
+fn html_message_escapes_content_and_label() {
+    let message = ChatMessage::new("m1", "s1", "user", "<script>alert('x')</script> & co");
+    let html = html_message("ผู้บัญชาการ", &message, &["data:image/png;base64,AAAA".into()]);
+
+    assert!(html.contains("&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; &amp; co"));
+    assert!(!html.contains("<script>"));
+    assert!(html.contains(r#"<img src="data:image/png;base64,AAAA""#));
+    assert_eq!(escape_html(r#""a""#), "&quot;a&quot;");
+}
+
+#[test]
+fn markdown_message_links_images_and_filename_is_safe() {
+    let message = ChatMessage::new("m1", "s1", "assistant", "สวัสดีค่ะ ผู้บัญชาการ\n");
+    let md = markdown_message("ราพี (Rapi)", &message, &["http://host/api/images/a.png".into()]);
+
+    assert!(md.starts_with("**ราพี (Rapi)** · "));
+    assert!(md.contains("สวัสดีค่ะ ผู้บัญชาการ\n\n![รูปที่แนบ](http://host/api/images/a.png)"));
+    assert_eq!(export_filename("tg:42@bot", ExportFormat::Jsonl), "rapi-chat-tg-42-bot.jsonl");
+}
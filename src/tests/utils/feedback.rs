@@ -0,0 +1,63 @@
+use chrono::Utc;
+
+use crate::app::config::Config;
+use crate::controllers::chat::{ChatMessage, Swipe};
+use crate::utils::dataset::RatingFilter;
+use crate::utils::feedback::{Feedback, FeedbackAggregator, Rating};
+
+fn feedback(rating: Rating, comment: Option<&str>) -> Option<Feedback> {
+    Some(Feedback { rating, comment: comment.map(str::to_string), timestamp: Utc::now() })
+}
+
+fn reply(id: &str, prompt_version: Option<&str>, rating: Option<Rating>) -> ChatMessage {
+    ChatMessage {
+        prompt_version: prompt_version.map(str::to_string),
+        feedback: rating.and_then(|r| feedback(r, None)),
+        ..ChatMessage::new(id, "s1", "assistant", "ค่ะ")
+    }
+}
+
+#[test]
+fn aggregates_by_persona_and_prompt_version_including_swipes() {
+    let config = Config::default();
+    let current = config.personas["rapi"].prompt_version();
+
+    let mut regenerated = reply("a2", Some(&current), Some(Rating::Up));
+    regenerated.swipes.push(Swipe {
+        content: "คำตอบเก่า".into(),
+        timestamp: Utc::now(),
+        persona: Some("rapi".into()),
+        prompt_version: Some("oldprmpt".into()),
+        feedback: feedback(Rating::Down, Some("เย็นชาเกินไป")),
+    });
+    let messages = vec![
+        ChatMessage::new("u1", "s1", "user", "สวัสดี"),
+        reply("a1", Some(&current), Some(Rating::Up)),
+        regenerated,
+        reply("a3", None, Some(Rating::Down)),
+        reply("a4", Some(&current), None),
+    ];
+
+    let mut aggregator = FeedbackAggregator::default();
+    aggregator.add_messages(&messages, None, None, None);
+    let stats = aggregator.finish(&config);
+
+    let find = |version: &str| stats.iter().find(|s| s.prompt_version == version).unwrap();
+    assert_eq!(stats.len(), 3);
+    assert_eq!((find(&current).up, find(&current).down, find(&current).current), (2, 0, true));
+    assert_eq!((find("oldprmpt").down, find("oldprmpt").comments, find("oldprmpt").current), (1, 1, false));
+    assert_eq!(find("unknown").score, 0.0);
+}
+
+#[test]
+fn rating_filter_rejects_any_thumbs_down() {
+    let liked = vec![reply("a1", None, Some(Rating::Up)), reply("a2", None, None)];
+    let mixed = vec![reply("a1", None, Some(Rating::Up)), reply("a2", None, Some(Rating::Down))];
+    let unrated = vec![reply("a1", None, None)];
+
+    assert!(RatingFilter::Up.matches(&liked));
+    assert!(!RatingFilter::Up.matches(&mixed));
+    assert!(!RatingFilter::Up.matches(&unrated));
+    assert!(RatingFilter::NotDown.matches(&unrated));
+    assert!(!RatingFilter::NotDown.matches(&mixed));
+}
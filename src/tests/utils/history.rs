@@ -0,0 +1,83 @@
+use chrono::{Duration, Utc};
+
+use crate::controllers::chat::ChatMessage;
+use crate::utils::history::{branch_path, leaves, normalize, resolve_leaf};
+
+fn msg(id: &str, parent: Option<&str>, role: &str, minutes: i64) -> ChatMessage {
+    ChatMessage {
+        parent_id: parent.map(str::to_string),
+        timestamp: Utc::now() + Duration::minutes(minutes),
+        ..ChatMessage::new(id, "s1", role, id)
+    }
+}
+
+fn ids(messages: &[ChatMessage]) -> Vec<&str> {
+    messages.iter().map(|m| m.id.as_str()).collect()
+}
+
+// [u1 -> a1 -> u2 -> a2 และแก้ u2 เป็น u2b -> a2b]
+fn forked() -> Vec<ChatMessage> {
+    vec![
+        msg("u1", None, "user", 0),
+        msg("a1", Some("u1"), "assistant", 1),
+        msg("u2", Some("a1"), "user", 2),
+        msg("a2", Some("u2"), "assistant", 3),
+        msg("u2b", Some("a1"), "user", 4),
+        msg("a2b", Some("u2b"), "assistant", 5),
+    ]
+}
+
+#[test]
+fn normalize_links_legacy_log_in_file_order() {
+    let mut messages = vec![
+        msg("", None, "user", 0),
+        msg("", None, "assistant", 1),
+        msg("", None, "user", 2),
+    ];
+
+    let mut again = messages.clone();
+
+    assert!(normalize("s1", &mut messages));
+    assert!(messages.iter().all(|m| !m.id.is_empty()));
+    assert_eq!(messages[0].parent_id, None);
+    assert_eq!(messages[1].parent_id.as_deref(), Some(messages[0].id.as_str()));
+    assert_eq!(messages[2].parent_id.as_deref(), Some(messages[1].id.as_str()));
+
+    assert!(!normalize("s1", &mut messages));
+
+    // [อ่านซ้ำโดยไม่ได้บันทึก ต้องได้ id เดิม]
+    normalize("s1", &mut again);
+    assert_eq!(ids(&again), ids(&messages));
+}
+
+#[test]
+fn branch_path_follows_active_leaf() {
+    let messages = forked();
+
+    assert_eq!(ids(&branch_path(&messages, Some("a2"))), ["u1", "a1", "u2", "a2"]);
+    assert_eq!(ids(&branch_path(&messages, Some("a2b"))), ["u1", "a1", "u2b", "a2b"]);
+}
+
+#[test]
+fn branch_path_falls_back_to_latest_message() {
+    let messages = forked();
+
+    assert_eq!(ids(&branch_path(&messages, None)), ["u1", "a1", "u2b", "a2b"]);
+    assert_eq!(ids(&branch_path(&messages, Some("missing"))), ["u1", "a1", "u2b", "a2b"]);
+}
+
+#[test]
+fn resolve_leaf_descends_to_newest_child() {
+    let messages = forked();
+
+    assert_eq!(resolve_leaf(&messages, "u2").as_deref(), Some("a2"));
+    assert_eq!(resolve_leaf(&messages, "a1").as_deref(), Some("a2b"));
+    assert_eq!(resolve_leaf(&messages, "missing"), None);
+}
+
+#[test]
+fn leaves_lists_every_branch_tip() {
+    let messages = forked();
+
+    assert_eq!(ids(&leaves(&messages).into_iter().cloned().collect::<Vec<_>>()), ["a2", "a2b"]);
+}
@@ -0,0 +1,106 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::controllers::chat::ChatMessage;
+use crate::utils::history::branch_path;
+use crate::utils::import::{parse_import, ImportFormat};
+
+fn contents(messages: &[ChatMessage]) -> Vec<&str> {
+    messages.iter().map(|m| m.content.as_str()).collect()
+}
+
+#[test]
+fn chatgpt_keeps_edit_branches_and_current_node() {
+    let node = |role: &str, text: &str, parent: Option<&str>, time: f64| json!({
+        "message": { "author": { "role": role }, "content": { "content_type": "text", "parts": [text] }, "create_time": time },
+        "parent": parent,
+    });
+    let body = json!([{
+        "title": "ทริปทะเล",
+        "create_time": 1700000000.0,
+        "current_node": "a2",
+        "mapping": {
+            "root": { "message": null, "parent": null },
+            "sys": node("system", "", Some("root"), 1700000000.0),
+            "u1": node("user", "ไปทะเลกัน", Some("sys"), 1700000001.0),
+            "a1": node("assistant", "ได้เลยค่ะ", Some("u1"), 1700000002.0),
+            "u2": node("user", "ไปภูเขาดีกว่า", Some("sys"), 1700000003.0),
+            "a2": node("assistant", "ภูเขาก็ดีค่ะ", Some("u2"), 1700000004.0),
+            "t1": node("tool", "search result", Some("a1"), 1700000005.0),
+        },
+    }]).to_string();
+
+    let parsed = parse_import(&body, ImportFormat::Auto).unwrap();
+    let session = &parsed.sessions[0];
+
+    assert_eq!(session.title.as_deref(), Some("ทริปทะเล"));
+    assert_eq!(session.messages.len(), 4);
+    let branch = branch_path(&session.messages, session.active_leaf.as_deref());
+    assert_eq!(contents(&branch), ["ไปภูเขาดีกว่า", "ภูเขาก็ดีค่ะ"]);
+    assert_eq!(parsed.skipped.len(), 1);
+    assert!(parsed.skipped[0].reason.contains("tool"));
+}
+
+#[test]
+fn sillytavern_links_lines_and_keeps_unselected_swipes() {
+    let body = [
+        json!({ "user_name": "Commander", "character_name": "Rapi", "create_date": "2024-06-03@17h04m00s" }).to_string(),
+        json!({ "name": "Commander", "is_user": true, "send_date": "June 3, 2024 5:04pm", "mes": "สวัสดี" }).to_string(),
+        json!({ "name": "Rapi", "is_user": false, "send_date": 1717434300000u64, "mes": "ค่ะ", "swipes": ["ครับ", "ค่ะ"] }).to_string(),
+        json!({ "name": "System", "is_system": true, "mes": "note" }).to_string(),
+        "{not json".to_string(),
+    ].join("\n");
+
+    let parsed = parse_import(&body, ImportFormat::Auto).unwrap();
+    let session = &parsed.sessions[0];
+
+    assert_eq!(session.title.as_deref(), Some("แชทกับ Rapi"));
+    assert_eq!(contents(&session.messages), ["สวัสดี", "ค่ะ"]);
+    assert_eq!(session.messages[0].role, "user");
+    assert_eq!(session.messages[1].parent_id.as_deref(), Some(session.messages[0].id.as_str()));
+    assert_eq!(session.messages[1].swipes.len(), 1);
+    assert_eq!(session.messages[1].swipes[0].content, "ครับ");
+    assert_eq!(session.messages[0].timestamp.to_rfc3339(), "2024-06-03T17:04:00+00:00");
+
+    let reasons: Vec<&str> = parsed.skipped.iter().map(|s| s.entry.as_str()).collect();
+    assert_eq!(reasons, ["line 4", "line 5"]);
+}
+
+#[test]
+fn own_export_gets_fresh_ids_with_links_preserved() {
+    let user = ChatMessage::new("11111111-1111-1111-1111-111111111111", "old", "user", "hi");
+    let reply = ChatMessage {
+        parent_id: Some(user.id.clone()),
+        ..ChatMessage::new("22222222-2222-2222-2222-222222222222", "old", "assistant", "hello")
+    };
+    let body = json!({
+        "version": 1,
+        "session_id": "old",
+        "active_leaf": reply.id,
+        "exported_at": "2026-01-01T00:00:00Z",
+        "messages": [user, reply],
+    }).to_string();
+
+    let parsed = parse_import(&body, ImportFormat::Auto).unwrap();
+    let session = &parsed.sessions[0];
+
+    assert_eq!(session.source_id.as_deref(), Some("old"));
+    assert_ne!(session.messages[0].id, "11111111-1111-1111-1111-111111111111");
+    assert_eq!(session.messages[1].parent_id.as_deref(), Some(session.messages[0].id.as_str()));
+    assert_eq!(session.active_leaf.as_deref(), Some(session.messages[1].id.as_str()));
+
+    assert!(parse_import("[]", ImportFormat::Auto).is_err());
+}
+
+#[test]
+fn malformed_client_json_is_a_bad_request() {
+    for (body, format) in [
+        ("not json at all", ImportFormat::Auto),
+        (r#"{"version": 1, "messages": "#, ImportFormat::Rapi),
+        ("[{", ImportFormat::Chatgpt),
+    ] {
+        let error = parse_import(body, format).unwrap_err();
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST, "{body}");
+        assert_eq!(error.code(), "invalid_json", "{body}");
+    }
+}
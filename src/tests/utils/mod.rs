@@ -0,0 +1,14 @@
+pub mod history;
+pub mod session_title;
+pub mod export;
+pub mod import;
+pub mod dataset;
+pub mod feedback;
+pub mod search_index;
+pub mod sparse;
+pub mod openai;
+pub mod model_router;
+pub mod session_lock;
+pub mod quota;
+pub mod chat_input;
+pub mod completion;
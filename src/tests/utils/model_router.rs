@@ -0,0 +1,55 @@
+use crate::app::config::Config;
+use crate::app::error::AppError;
+use crate::app::persona::ModelTarget;
+use crate::utils::completion::GenerationParams;
+use crate::utils::model_router::{ModelRouter, Task, TaskRoute};
+
+fn models(router: &ModelRouter, task: Task) -> Vec<(String, String)> {
+    router.route(task).models.iter().map(|t| (t.provider.clone(), t.model.clone())).collect()
+}
+
+#[test]
+fn background_tasks_default_to_the_chat_model() {
+    let mut config = Config::default();
+    config.openai.model = "llama3.1".into();
+    let router = ModelRouter::new(&config);
+
+    for task in Task::ALL {
+        assert_eq!(models(&router, task), vec![("openai".to_string(), "llama3.1".to_string())], "{}", task.as_str());
+    }
+}
+
+#[test]
+fn unset_tasks_follow_the_chat_route_and_explicit_routes_win() {
+    let target = |provider: &str, model: &str| ModelTarget { provider: provider.into(), model: model.into(), vision: true };
+    let mut config = Config::default();
+    config.routing.insert(Task::Chat, TaskRoute { models: vec![target("ollama", "qwen2.5")], ..Default::default() });
+    config.routing.insert(Task::Summarization, TaskRoute { models: vec![target("openai", "gpt-4o-mini")], ..Default::default() });
+    let router = ModelRouter::new(&config);
+
+    assert_eq!(models(&router, Task::TitleGeneration), vec![("ollama".to_string(), "qwen2.5".to_string())]);
+    assert_eq!(models(&router, Task::Summarization), vec![("openai".to_string(), "gpt-4o-mini".to_string())]);
+}
+
+#[test]
+fn chat_route_rejects_nan_params() {
+    let config = Config::default();
+    let router = ModelRouter::new(&config);
+    let persona = config.persona("rapi").unwrap();
+    let params = GenerationParams { temperature: Some(f32::NAN), ..Default::default() };
+
+    assert!(matches!(router.chat_route(persona, false, &params), Err(AppError::BadRequest(_))));
+}
+
+#[test]
+fn moderation_and_fact_extraction_are_routable() {
+    let config: Config = toml::from_str(r#"
+        [routing.moderation]
+        models = [{ provider = "openai", model = "omni-moderation-latest" }]
+    "#).unwrap();
+    let router = ModelRouter::new(&config);
+
+    assert_eq!(models(&router, Task::Moderation), vec![("openai".to_string(), "omni-moderation-latest".to_string())]);
+    assert_eq!(models(&router, Task::FactExtraction), models(&router, Task::Chat));
+    assert_eq!(Task::FactExtraction.as_str(), "fact_extraction");
+}
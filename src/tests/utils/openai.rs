@@ -0,0 +1,83 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::routing::post;
+use axum::Router;
+use chrono::{TimeZone, Utc};
+use reqwest::StatusCode;
+
+use crate::app::config::ResilienceConfig;
+use crate::utils::completion::is_final;
+use crate::utils::openai::{classify_error, parse_retry_after, OpenAiClient};
+
+fn error_body(code: &str) -> String {
+    format!(r#"{{"error": {{"message": "nope", "type": "invalid_request_error", "code": "{code}"}}}}"#)
+}
+
+#[test]
+fn upstream_client_errors_stop_the_fallback_chain() {
+    for status in [StatusCode::BAD_REQUEST, StatusCode::NOT_FOUND, StatusCode::UNPROCESSABLE_ENTITY] {
+        assert!(is_final(&classify_error(status, &error_body("invalid_value"))), "{status}");
+    }
+    assert!(is_final(&classify_error(StatusCode::UNAUTHORIZED, "")));
+    assert!(is_final(&classify_error(StatusCode::BAD_REQUEST, &error_body("context_length_exceeded"))));
+}
+
+#[test]
+fn timeouts_rate_limits_and_outages_fall_back() {
+    for status in [
+        StatusCode::REQUEST_TIMEOUT,
+        StatusCode::TOO_MANY_REQUESTS,
+        StatusCode::INTERNAL_SERVER_ERROR,
+        StatusCode::SERVICE_UNAVAILABLE,
+    ] {
+        assert!(!is_final(&classify_error(status, "")), "{status}");
+    }
+    assert!(!is_final(&classify_error(StatusCode::TOO_MANY_REQUESTS, &error_body("insufficient_quota"))));
+}
+
+#[test]
+fn retry_after_accepts_seconds_and_http_dates() {
+    let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 28, 0).unwrap();
+
+    assert_eq!(parse_retry_after(" 120 ", now), Some(Duration::from_secs(120)));
+    assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now), Some(Duration::from_secs(30)));
+    assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(Duration::ZERO));
+    assert_eq!(parse_retry_after("soon", now), None);
+}
+
+// [server ที่ส่ง chunk ทุก 150ms รวมแล้วนานกว่า timeout ของ call]
+async fn slow_stream_server() -> String {
+    let app = Router::new().route("/chat/completions", post(|| async {
+        let chunks = tokio_stream::StreamExt::throttle(
+            tokio_stream::iter((0..4).map(|i| Ok::<_, Infallible>(format!("data: {i}\n")))),
+            Duration::from_millis(150),
+        );
+        Body::from_stream(chunks)
+    }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    format!("http://{addr}")
+}
+
+#[tokio::test]
+async fn stream_is_not_cut_by_the_call_timeout() {
+    let base_url = slow_stream_server().await;
+    // [client หลักมี timeout รวมสั้นกว่า stream ทั้งเส้น ต้องไม่ถูกใช้กับ stream]
+    let http = reqwest::Client::builder().timeout(Duration::from_millis(300)).build().unwrap();
+    let client = OpenAiClient::new("test", http, reqwest::Client::new(), &base_url, "", &ResilienceConfig::default());
+
+    let mut response = client
+        .post_stream("/chat/completions", &serde_json::json!({}), Duration::from_millis(300))
+        .await
+        .unwrap();
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.unwrap() {
+        body.extend_from_slice(&chunk);
+    }
+    assert_eq!(String::from_utf8(body).unwrap(), "data: 0\ndata: 1\ndata: 2\ndata: 3\n");
+}
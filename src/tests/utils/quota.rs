@@ -0,0 +1,42 @@
+use crate::utils::quota::{QuotaLimits, QuotaManager, QuotaSettings};
+
+fn temp_path() -> String {
+    std::env::temp_dir()
+        .join(format!("rapi-quota-{}.json", uuid::Uuid::new_v4()))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+fn limits(daily_tokens: u64) -> QuotaLimits {
+    QuotaLimits { daily_tokens: Some(daily_tokens), ..Default::default() }
+}
+
+#[tokio::test]
+async fn persists_usage_and_overrides_but_reads_prices_from_config() {
+    let path = temp_path();
+    let quota = QuotaManager::load(QuotaSettings::default(), &path).unwrap();
+    quota.record("alice", 100, 50);
+    quota.update_overrides(|o| o.kill_switch = Some(true));
+    quota.persist().await.unwrap();
+
+    let config = QuotaSettings { price_input_per_1k_usd: 1.0, user: limits(10), ..Default::default() };
+    let reloaded = QuotaManager::load(config, &path).unwrap();
+
+    assert_eq!(reloaded.usage("alice").day_tokens, 150);
+    assert!(reloaded.settings().kill_switch);
+    assert_eq!(reloaded.settings().price_input_per_1k_usd, 1.0);
+    assert_eq!(reloaded.settings().user, limits(10));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn corrupt_file_fails_instead_of_resetting_usage() {
+    let path = temp_path();
+    std::fs::write(&path, "{\"global\": {\"day\": ").unwrap();
+
+    assert!(QuotaManager::load(QuotaSettings::default(), &path).is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+
+use crate::controllers::chat::ChatMessage;
+use crate::utils::search_index::{highlight, SearchIndex, SearchQuery};
+
+fn msg(id: &str, session_id: &str, role: &str, content: &str, days_ago: i64) -> ChatMessage {
+    ChatMessage {
+        timestamp: Utc::now() - Duration::days(days_ago),
+        ..ChatMessage::new(id, session_id, role, content)
+    }
+}
+
+async fn index() -> Arc<SearchIndex> {
+    let index = Arc::new(SearchIndex::open(":memory:").unwrap());
+    index.index("alice", vec![
+        msg("1", "s1", "user", "ผู้บัญชาการชวนราพีไปเที่ยวทะเลวันเสาร์", 10),
+        msg("2", "s1", "assistant", "ทะเลก็ดีค่ะ แต่ต้องเตรียมครีมกันแดด", 10),
+    ]).await.unwrap();
+    index.index("alice", vec![
+        msg("3", "s2", "user", "Let's go to the BEACH tomorrow", 1),
+        msg("4", "s2", "summary", "ทะเล summary ไม่ควรถูกค้นเจอ", 1),
+    ]).await.unwrap();
+    index.index("bob", vec![msg("5", "s3", "user", "ทะเลของ bob", 1)]).await.unwrap();
+    index
+}
+
+fn query(text: &str) -> SearchQuery {
+    SearchQuery { text: text.into(), owner: "alice".into(), limit: 10, ..Default::default() }
+}
+
+#[tokio::test]
+async fn finds_thai_substrings_without_word_breaks() {
+    let index = index().await;
+
+    let results = index.search(query("ทะเล")).await.unwrap();
+    assert_eq!(results.total, 2);
+    assert!(results.hits.iter().all(|h| h.session_id == "s1"));
+    assert!(results.hits.iter().any(|h| h.highlight.contains("เที่ยว<mark>ทะเล</mark>วันเสาร์")));
+
+    let results = index.search(query("beach")).await.unwrap();
+    assert_eq!(results.hits[0].highlight, "Let&#39;s go to the <mark>BEACH</mark> tomorrow");
+}
+
+#[tokio::test]
+async fn filters_by_role_date_and_short_terms() {
+    let index = index().await;
+
+    let assistant_only = SearchQuery { role: Some("assistant".into()), ..query("ทะเล") };
+    assert_eq!(index.search(assistant_only).await.unwrap().hits[0].message_id, "2");
+
+    let recent = SearchQuery { since: Some(Utc::now() - Duration::days(3)), ..query("to") };
+    let results = index.search(recent).await.unwrap();
+    assert_eq!((results.total, results.hits[0].message_id.as_str()), (1, "3"));
+
+    // [แทนที่ข้อความเดิมด้วย id เดิม]
+    index.index("alice", vec![msg("2", "s1", "assistant", "ไปภูเขาแทนนะคะ", 10)]).await.unwrap();
+    assert_eq!(index.search(query("ทะเล")).await.unwrap().total, 1);
+
+    index.delete_session("s1").await.unwrap();
+    assert_eq!(index.search(query("ภูเขา")).await.unwrap().total, 0);
+}
+
+#[tokio::test]
+async fn only_searches_the_owners_sessions() {
+    let index = index().await;
+
+    let bob = SearchQuery { owner: "bob".into(), ..query("ทะเล") };
+    let results = index.search(bob).await.unwrap();
+    assert_eq!((results.total, results.hits[0].session_id.as_str()), (1, "s3"));
+
+    let unscoped = SearchQuery { owner: String::new(), ..query("ทะเล") };
+    assert!(index.search(unscoped).await.is_err());
+
+    let everyone = SearchQuery { owner: String::new(), all_owners: true, ..query("ทะเล") };
+    assert_eq!(index.search(everyone).await.unwrap().total, 3);
+
+    index.delete_session("s1").await.unwrap();
+    assert_eq!(index.session_ids().await.unwrap().len(), 2);
+}
+
+#[test]
+fn highlight_trims_long_messages_around_first_match() {
+    let content = format!("{}ราพี{}", "ก".repeat(100), "ข".repeat(200));
+    let out = highlight(&content, &["ราพี"]);
+
+    assert!(out.starts_with('…') && out.ends_with('…'));
+    assert!(out.contains("<mark>ราพี</mark>"));
+}
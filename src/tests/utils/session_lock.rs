@@ -0,0 +1,27 @@
+use crate::controllers::chat::{load_full_messages, save_messages, ChatMessage};
+use crate::utils::session_lock::lock_session;
+
+#[tokio::test]
+async fn concurrent_appends_under_session_lock_keep_every_message() {
+    let dir = std::env::temp_dir().join(format!("rapi-lock-{}", uuid::Uuid::new_v4()));
+    let dir = dir.to_str().unwrap().to_string();
+
+    let writers: Vec<_> = (0..16)
+        .map(|i| {
+            let dir = dir.clone();
+            tokio::spawn(async move {
+                let _lock = lock_session("lock-test").await;
+                let message = ChatMessage::new(&format!("m{i}"), "lock-test", "user", "hi");
+                save_messages(&dir, "lock-test", vec![message]).await.unwrap();
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.await.unwrap();
+    }
+
+    let messages = load_full_messages(&dir, "lock-test").await.unwrap();
+    assert_eq!(messages.len(), 16);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,14 @@
+use crate::utils::session_title::clean_title;
+
+#[test]
+fn clean_title_strips_quotes_prefix_and_extra_lines() {
+    assert_eq!(clean_title("\n\"ทริปทะเลกับราพี\"\nคำอธิบายเพิ่มเติม").as_deref(), Some("ทริปทะเลกับราพี"));
+    assert_eq!(clean_title("Title: Planning a beach trip.").as_deref(), Some("Planning a beach trip"));
+    assert_eq!(clean_title("**「作戦会議」**").as_deref(), Some("作戦会議"));
+}
+
+#[test]
+fn clean_title_rejects_empty_and_caps_length() {
+    assert_eq!(clean_title("  \n \"\" "), None);
+    assert_eq!(clean_title(&"ก".repeat(200)).map(|t| t.chars().count()), Some(60));
+}
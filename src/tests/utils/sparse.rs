@@ -0,0 +1,36 @@
+use crate::utils::sparse::{document_vector, query_vector, tokenize};
+
+#[test]
+fn tokenize_keeps_names_and_numbers_whole_and_splits_thai_into_trigrams() {
+    assert_eq!(
+        tokenize("Unit-7 ออก 12/05/2024 กับ NIKKE"),
+        vec!["unit", "7", "ออก", "12", "05", "2024", "กับ", "nikke"],
+    );
+    assert_eq!(tokenize("ทะเลสวย"), vec!["ทะเ", "ะเล", "เลส", "ลสว", "สวย"]);
+    // [วรรณยุกต์เป็น combining mark ต้องอยู่ในคำเดียวกัน]
+    assert_eq!(tokenize("ที่นี่"), vec!["ที่", "ี่น", "่นี", "นี่"]);
+    assert_eq!(tokenize("Rapiกับ"), vec!["rapi", "กับ"]);
+    assert!(tokenize("?! ...").is_empty());
+}
+
+#[test]
+fn document_vector_is_sorted_unique_and_saturates_repeats() {
+    let vector = document_vector("alpha alpha alpha beta");
+    assert_eq!(vector.len(), 2);
+    assert!(vector.windows(2).all(|w| w[0].0 < w[1].0));
+
+    let weight = |v: &[(u32, f32)], term: &str| {
+        let index = query_vector(term)[0].0;
+        v.iter().find(|(i, _)| *i == index).unwrap().1
+    };
+    let alpha = weight(&vector, "alpha");
+    let beta = weight(&vector, "beta");
+    assert!(alpha > beta && alpha < 3.0 * beta);
+}
+
+#[test]
+fn query_vector_counts_each_term_once() {
+    let vector = query_vector("Unit 7 unit");
+    assert_eq!(vector.len(), 2);
+    assert!(vector.iter().all(|(_, w)| *w == 1.0));
+}
@@ -0,0 +1,265 @@
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+
+use axum::extract::{FromRequest, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::Json;
+use axum_extra::extract::multipart::Field;
+use axum_extra::extract::Multipart;
+use base64::engine::general_purpose;
+use base64::Engine as _;
+use serde::Deserialize;
+use tracing::{info_span, Instrument};
+use uuid::Uuid;
+
+use crate::app::auth::identify;
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+use crate::app::state::AppState;
+use crate::utils::completion::GenerationParams;
+use crate::utils::image::{get_ext_file_or_default, get_filename_or_default};
+
+// [ให้ client ที่ส่ง body แบบ OpenAI เลือก session ผ่าน header ได้]
+pub const SESSION_ID_HEADER: &str = "x-session-id";
+
+#[derive(Debug, Clone)]
+pub enum ChatImage {
+    // [ไฟล์ที่บันทึกไว้ใน images_dir แล้ว]
+    Stored(String),
+    // [URL ภายนอก ส่งต่อให้โมเดลโหลดเอง]
+    Url(String),
+}
+
+impl ChatImage {
+    // [แปลงกลับจาก ChatMessage.attachments]
+    pub fn from_attachment(attachment: &str) -> Self {
+        if attachment.starts_with("https://") || attachment.starts_with("http://") {
+            ChatImage::Url(attachment.to_string())
+        } else {
+            ChatImage::Stored(attachment.to_string())
+        }
+    }
+}
+
+// -----------------------
+// ChatInput
+// /api/chat รับได้ทั้ง multipart/form-data และ application/json แล้วแปลงมาเป็นแบบเดียวกัน
+// -----------------------
+#[derive(Debug)]
+pub struct ChatInput {
+    pub session_id: String,
+    pub message: String,
+    // [user ที่ยืนยันตัวตนแล้ว (Caller) ใช้นับโควต้า ไม่รับจาก body]
+    pub user_id: String,
+    pub persona: Option<String>,
+    pub images: Vec<ChatImage>,
+    pub params: GenerationParams,
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonChatBody {
+    #[serde(default)]
+    session_id: String,
+    #[serde(default)]
+    message: String,
+    persona: Option<String>,
+    // [แต่ละรูปเป็น https://..., data:image/...;base64,... หรือ base64 เปล่า ๆ]
+    #[serde(default)]
+    images: Vec<String>,
+    #[serde(flatten)]
+    params: GenerationParams,
+}
+
+impl FromRequest<Arc<AppState>> for ChatInput {
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let caller = identify(&state.config, req.headers())?;
+        let content_type = req.headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        let mut input = if content_type.starts_with("application/json") {
+            let Json(body) = Json::<JsonChatBody>::from_request(req, state).await?;
+            from_json(body, &state.config.storage.images_dir)
+                .instrument(info_span!("chat.json_parse"))
+                .await?
+        } else if content_type.starts_with("multipart/form-data") {
+            let multipart = Multipart::from_request(req, state).await?;
+            from_multipart(multipart, &state.config.storage.images_dir)
+                .instrument(info_span!("chat.multipart_parse"))
+                .await?
+        } else {
+            return Err(AppError::UnsupportedMediaType(
+                "Expected application/json or multipart/form-data".into(),
+            ));
+        };
+
+        input.user_id = caller.user_id;
+        input.validate(state.config.limits.max_images)?;
+
+        Ok(input)
+    }
+}
+
+impl ChatInput {
+    pub fn validate(&self, max_images: usize) -> AppResult<()> {
+        if self.session_id.trim().is_empty() {
+            return Err(AppError::BadRequest("Missing session_id".into()));
+        }
+        check_session_id(&self.session_id)?;
+        if self.message.trim().is_empty() && self.images.is_empty() {
+            return Err(AppError::BadRequest("Message or image is required".into()));
+        }
+        if self.images.len() > max_images {
+            return Err(AppError::BadRequest(format!("At most {max_images} images per message")));
+        }
+
+        Ok(())
+    }
+}
+
+// [session_id ถูกใช้เป็นชื่อไฟล์ ห้ามมี / หรือ .. หลุดออกนอกโฟลเดอร์]
+pub fn check_session_id(session_id: &str) -> AppResult<()> {
+    let valid = !session_id.is_empty()
+        && session_id.len() <= 128
+        && !session_id.starts_with('.')
+        && session_id.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '@'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!("Invalid session_id '{session_id}'")))
+    }
+}
+
+async fn from_json(body: JsonChatBody, images_dir: &str) -> AppResult<ChatInput> {
+    Ok(ChatInput {
+        session_id: body.session_id,
+        message: body.message,
+        user_id: String::new(),
+        persona: body.persona.filter(|p| !p.is_empty()),
+        images: resolve_images(body.images, images_dir).await?,
+        params: body.params,
+    })
+}
+
+// [URL ส่งต่อให้โมเดล ส่วน base64/data URL บันทึกลง images_dir เหมือนรูปจาก multipart]
+pub async fn resolve_images(raw_images: Vec<String>, images_dir: &str) -> AppResult<Vec<ChatImage>> {
+    let mut images = Vec::new();
+
+    for raw in raw_images {
+        let raw = raw.trim();
+
+        if raw.starts_with("https://") || raw.starts_with("http://") {
+            images.push(ChatImage::Url(raw.to_string()));
+            continue;
+        }
+
+        // [data URL ให้ตัด header ออกเหลือแต่ base64]
+        let encoded = match raw.strip_prefix("data:") {
+            Some(rest) => rest.split_once(";base64,")
+                .map(|(_, data)| data)
+                .ok_or_else(|| AppError::BadRequest("Image data URL must be base64 encoded".into()))?,
+            None => raw,
+        };
+
+        let data = general_purpose::STANDARD.decode(encoded)
+            .map_err(|e| AppError::BadRequest(format!("Invalid base64 image: {e}")))?;
+
+        images.push(ChatImage::Stored(save_image(&data, None, images_dir).await?));
+    }
+
+    Ok(images)
+}
+
+async fn from_multipart(mut multipart: Multipart, images_dir: &str) -> AppResult<ChatInput> {
+    let mut input = ChatInput {
+        session_id: String::new(),
+        message: String::new(),
+        user_id: String::new(),
+        persona: None,
+        images: Vec::new(),
+        params: GenerationParams::default(),
+    };
+
+    while let Some(field) = multipart.next_field().await? {
+        match field.name().unwrap_or_default() {
+            "message" => {
+                input.message = field.text().await.map_err(
+                    |e| AppError::BadRequest(format!("Invalid text: {e}"))
+                )?;
+            }
+            "session_id" => {
+                input.session_id = field.text().await.unwrap_or_default();
+            }
+            "persona" => {
+                input.persona = Some(field.text().await.unwrap_or_default()).filter(|p| !p.is_empty());
+            }
+            "temperature" => input.params.temperature = parse_param(field).await?,
+            "top_p" => input.params.top_p = parse_param(field).await?,
+            "max_tokens" => input.params.max_tokens = parse_param(field).await?,
+            "presence_penalty" => input.params.presence_penalty = parse_param(field).await?,
+            "seed" => input.params.seed = parse_param(field).await?,
+            // [ส่ง stop ซ้ำได้หลาย field]
+            "stop" => {
+                let stop = field.text().await.unwrap_or_default();
+                input.params.stop.get_or_insert_with(Vec::new).push(stop);
+            }
+            // [ส่ง image ได้หลาย field]
+            "image" => {
+                let filename_raw = get_filename_or_default(&field)?;
+                let ext = get_ext_file_or_default(&filename_raw)?;
+
+                let data = field.bytes().await?;
+
+                if data.is_empty() {
+                    continue;
+                }
+
+                input.images.push(ChatImage::Stored(save_image(&data, Some(ext), images_dir).await?));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(input)
+}
+
+// [field ว่าง = ไม่ได้ส่งมา]
+async fn parse_param<T: std::str::FromStr>(field: Field) -> AppResult<Option<T>> {
+    let name = field.name().unwrap_or_default().to_string();
+    let raw = field.text().await?;
+
+    if raw.trim().is_empty() {
+        return Ok(None);
+    }
+
+    raw.trim().parse()
+        .map(Some)
+        .map_err(|_| AppError::BadRequest(format!("Invalid {name}: '{raw}'")))
+}
+
+// [เช็คว่าเป็นรูปจริงแล้วเขียนผ่านไฟล์ .tmp ก่อน rename กันไฟล์ครึ่ง ๆ]
+async fn save_image(data: &[u8], ext: Option<String>, images_dir: &str) -> AppResult<String> {
+    let kind = infer::get(data)
+        .ok_or_else(|| AppError::BadRequest("Unknown file type".into()))?;
+
+    if !kind.mime_type().starts_with("image/") {
+        return Err(AppError::BadRequest("Uploaded file is not an image".into()));
+    }
+
+    let ext = ext.unwrap_or_else(|| kind.extension().to_string());
+    let filename = format!("chat-{}.{}", Uuid::new_v4(), ext);
+    let filepath = format!("{}/{}", images_dir, filename);
+
+    let tmp_path = format!("{}/.tmp-{}", images_dir, filename);
+    let mut tmp_file = File::create(&tmp_path)?;
+    tmp_file.write_all(data)?;
+    tokio::fs::rename(tmp_path, &filepath).await?;
+
+    Ok(filepath)
+}
@@ -0,0 +1,343 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info_span, warn, Instrument};
+
+use crate::app::config::GenerationLimits;
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+use crate::app::state::AppState;
+use crate::controllers::chat::MessageRequest;
+use crate::utils::metrics::{record_openai, record_tokens};
+use crate::utils::model_router::{Task, TaskRoute};
+
+#[derive(Serialize, Debug)]
+struct RequestBody<'a> {
+    model: &'a str,
+    messages: &'a [MessageRequest],
+    #[serde(flatten)]
+    params: &'a GenerationParams,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+#[derive(Serialize, Debug)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+// [ค่าที่ส่งต่อให้ provider ตรง ๆ ไม่ใส่ = ใช้ค่า default ของ provider]
+// [ไม่ใช้ deny_unknown_fields: ถูก flatten ใน body ของ request key อื่นที่ client ส่งมา (เช่น user_id ของ client รุ่นเก่า) จะกลายเป็น 400
+//  ต่างจาก multipart ที่ข้าม field ที่ไม่รู้จักไป]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct GenerationParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+}
+
+impl GenerationParams {
+    // [ค่าที่ other ใส่มาจะทับค่าเดิม: route -> persona -> request]
+    pub fn merge(&self, other: &GenerationParams) -> GenerationParams {
+        GenerationParams {
+            temperature: other.temperature.or(self.temperature),
+            top_p: other.top_p.or(self.top_p),
+            max_tokens: other.max_tokens.or(self.max_tokens),
+            presence_penalty: other.presence_penalty.or(self.presence_penalty),
+            seed: other.seed.or(self.seed),
+            stop: other.stop.clone().or_else(|| self.stop.clone()),
+        }
+    }
+
+    // [NaN/inf ผ่าน f32::clamp ไปได้ ต้องตรวจก่อนบีบ คืนชื่อ field แรกที่ไม่ใช่ตัวเลขจริง]
+    pub fn non_finite(&self) -> Option<&'static str> {
+        [
+            ("temperature", self.temperature),
+            ("top_p", self.top_p),
+            ("presence_penalty", self.presence_penalty),
+        ]
+        .into_iter()
+        .find(|(_, value)| value.is_some_and(|v| !v.is_finite()))
+        .map(|(name, _)| name)
+    }
+
+    // [บีบให้อยู่ในขอบเขตที่ server อนุญาต ไม่ตอบ error เพราะ client ส่งเกินมานิดหน่อย]
+    pub fn clamp(mut self, limits: &GenerationLimits) -> GenerationParams {
+        self.temperature = self.temperature.map(|v| v.clamp(0.0, limits.temperature_max));
+        self.top_p = self.top_p.map(|v| v.clamp(limits.top_p_min, 1.0));
+        self.max_tokens = self.max_tokens.map(|v| v.clamp(1, limits.max_tokens));
+        self.presence_penalty = self.presence_penalty
+            .map(|v| v.clamp(-limits.presence_penalty_max, limits.presence_penalty_max));
+        self.stop = self.stop
+            .map(|stop| {
+                stop.into_iter()
+                    .filter(|s| !s.is_empty())
+                    .take(limits.max_stop)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|stop| !stop.is_empty());
+        self
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAiResponse {
+    choices: Vec<OpenAiResponseChoice>,
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct OpenAiUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAiResponseChoice {
+    message: ChoiceMessage,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChoiceMessage {
+    content: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct StreamChoice {
+    delta: StreamDelta,
+}
+
+#[derive(Deserialize, Debug)]
+struct StreamDelta {
+    content: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Completion {
+    pub reply: String,
+    pub usage: Option<OpenAiUsage>,
+    pub provider: String,
+    pub model: String,
+}
+
+// [error ที่เปลี่ยนโมเดลไปก็ไม่ช่วย ให้หยุดเลย: 4xx จาก provider ยกเว้น 408/429 (ดู classify_error)]
+pub fn is_final(error: &AppError) -> bool {
+    matches!(
+        error,
+        AppError::ContentPolicy(_)
+            | AppError::ContextLengthExceeded(_)
+            | AppError::UpstreamAuth(_)
+            | AppError::UpstreamRejected(_)
+    )
+}
+
+// -----------------------
+// ลองโมเดลตามลำดับ (เช่น gpt-4o -> gpt-4o-mini -> ollama) จนกว่าจะมีตัวตอบได้
+// -----------------------
+pub async fn complete_with_fallback(
+    state: &AppState,
+    task: Task,
+    route: &TaskRoute,
+    messages: &[MessageRequest],
+) -> AppResult<Completion> {
+    let resilience = &state.config.resilience;
+    let timeout = Duration::from_secs(match task {
+        Task::Summarization => resilience.summary_timeout_secs,
+        _ => resilience.chat_timeout_secs,
+    });
+    let mut last_error = AppError::InternalError("No model configured".into());
+
+    for target in &route.models {
+        let Some(client) = state.providers.get(&target.provider) else {
+            warn!(provider = %target.provider, "Unknown provider in model chain");
+            continue;
+        };
+
+        let started = Instant::now();
+        let result = client
+            .post_json::<_, OpenAiResponse>(
+                "/chat/completions",
+                &RequestBody {
+                    model: &target.model,
+                    messages,
+                    params: &route.params,
+                    stream: false,
+                    stream_options: None,
+                },
+                timeout,
+            )
+            .instrument(info_span!("chat.completion", task = task.as_str(), provider = %target.provider, model = %target.model))
+            .await;
+        record_openai(task.as_str(), started.elapsed(), result.is_ok());
+
+        match result {
+            Ok(res) => {
+                if let Some(usage) = &res.usage {
+                    record_tokens(&target.model, usage.prompt_tokens, usage.completion_tokens);
+                }
+
+                let reply = res.choices.first()
+                    .map(|choices: &OpenAiResponseChoice| choices.message.content.clone())
+                    .unwrap_or_else(|| "No response".to_string());
+
+                return Ok(Completion {
+                    reply,
+                    usage: res.usage,
+                    provider: target.provider.clone(),
+                    model: target.model.clone(),
+                });
+            }
+            Err(e) if is_final(&e) => return Err(e),
+            Err(e) => {
+                warn!(provider = %target.provider, model = %target.model, error = %e, "Model failed, trying next");
+                last_error = e;
+            }
+        }
+    }
+
+    Err(last_error)
+}
+
+// -----------------------
+// Streaming
+// fallback ได้เฉพาะก่อนได้ response แรก หลังจากเริ่มส่ง token แล้วเปลี่ยนโมเดลไม่ได้
+// -----------------------
+pub struct CompletionStream {
+    pub provider: String,
+    pub model: String,
+    pub usage: Option<OpenAiUsage>,
+    response: reqwest::Response,
+    buffer: Vec<u8>,
+    done: bool,
+    // [ไม่มี chunk ใหม่นานเกินนี้ถือว่า provider ค้าง, คำตอบยาวแค่ไหนก็ได้ขอให้ยังส่งมาเรื่อย ๆ]
+    idle_timeout: Duration,
+}
+
+impl CompletionStream {
+    // [คืนข้อความทีละชิ้น None = จบ stream]
+    pub async fn next_delta(&mut self) -> AppResult<Option<String>> {
+        loop {
+            if let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+
+                let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    self.done = true;
+                    return Ok(None);
+                }
+
+                let chunk: StreamChunk = serde_json::from_str(data)
+                    .map_err(|e| AppError::UpstreamError(format!("Unexpected stream chunk: {e}")))?;
+
+                if let Some(usage) = chunk.usage {
+                    record_tokens(&self.model, usage.prompt_tokens, usage.completion_tokens);
+                    self.usage = Some(usage);
+                }
+
+                let content: String = chunk.choices.into_iter()
+                    .filter_map(|c| c.delta.content)
+                    .collect();
+                if !content.is_empty() {
+                    return Ok(Some(content));
+                }
+                continue;
+            }
+
+            if self.done {
+                return Ok(None);
+            }
+
+            // [ตัดบรรทัดจาก byte ไม่ใช่ string เพราะตัวอักษรไทยอาจถูกแบ่งข้าม chunk]
+            let chunk = tokio::time::timeout(self.idle_timeout, self.response.chunk())
+                .await
+                .map_err(|_| AppError::UpstreamUnavailable(format!(
+                    "Stream stalled for {}s", self.idle_timeout.as_secs_f32()
+                )))??;
+            match chunk {
+                Some(bytes) => self.buffer.extend_from_slice(&bytes),
+                None => {
+                    self.done = true;
+                    self.buffer.push(b'\n');
+                }
+            }
+        }
+    }
+}
+
+pub async fn stream_with_fallback(
+    state: &AppState,
+    task: Task,
+    route: &TaskRoute,
+    messages: &[MessageRequest],
+) -> AppResult<CompletionStream> {
+    let timeout = Duration::from_secs(state.config.resilience.chat_timeout_secs);
+    let idle_timeout = Duration::from_secs(state.config.resilience.stream_idle_secs);
+    let mut last_error = AppError::InternalError("No model configured".into());
+
+    for target in &route.models {
+        let Some(client) = state.providers.get(&target.provider) else {
+            warn!(provider = %target.provider, "Unknown provider in model chain");
+            continue;
+        };
+
+        let started = Instant::now();
+        let result = client
+            .post_stream(
+                "/chat/completions",
+                &RequestBody {
+                    model: &target.model,
+                    messages,
+                    params: &route.params,
+                    stream: true,
+                    stream_options: Some(StreamOptions { include_usage: true }),
+                },
+                timeout,
+            )
+            .instrument(info_span!("chat.completion", task = task.as_str(), provider = %target.provider, model = %target.model, stream = true))
+            .await;
+        record_openai(task.as_str(), started.elapsed(), result.is_ok());
+
+        match result {
+            Ok(response) => {
+                return Ok(CompletionStream {
+                    provider: target.provider.clone(),
+                    model: target.model.clone(),
+                    usage: None,
+                    response,
+                    buffer: Vec::new(),
+                    done: false,
+                    idle_timeout,
+                });
+            }
+            Err(e) if is_final(&e) => return Err(e),
+            Err(e) => {
+                warn!(provider = %target.provider, model = %target.model, error = %e, "Model failed, trying next");
+                last_error = e;
+            }
+        }
+    }
+
+    Err(last_error)
+}
@@ -0,0 +1,155 @@
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::app::config::CorsConfig;
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+use crate::utils::chat_input::SESSION_ID_HEADER;
+use crate::utils::telemetry::REQUEST_ID_HEADER;
+
+// -----------------------
+// Origin rule
+// รองรับ: "*", "https://example.com", "https://*.netlify.app", "https://*--site.netlify.app", "http://localhost:*"
+// -----------------------
+#[derive(Debug, Clone, PartialEq)]
+pub enum OriginRule {
+    Any,
+    Pattern {
+        scheme: String,
+        host: HostRule,
+        port: PortRule,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostRule {
+    Exact(String),
+    // [*.netlify.app -> ".netlify.app", *--site.netlify.app -> "--site.netlify.app"]
+    Suffix(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PortRule {
+    Default,
+    Exact(u16),
+    Any,
+}
+
+impl OriginRule {
+    pub fn parse(raw: &str) -> AppResult<Self> {
+        let invalid = |why: &str| AppError::ConfigError(format!("Invalid CORS origin '{raw}': {why}"));
+
+        if raw == "*" {
+            return Ok(OriginRule::Any);
+        }
+
+        let (scheme, rest) = raw.split_once("://")
+            .ok_or_else(|| invalid("missing scheme, expected e.g. https://example.com"))?;
+
+        if scheme != "http" && scheme != "https" {
+            return Err(invalid("scheme must be http or https"));
+        }
+        if rest.is_empty() || rest.contains('/') {
+            return Err(invalid("must not contain a path or trailing slash"));
+        }
+
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((host, "*")) => (host, PortRule::Any),
+            Some((host, port)) => (host, PortRule::Exact(port.parse().map_err(|_| invalid("bad port"))?)),
+            None => (rest, PortRule::Default),
+        };
+
+        let host = match host.strip_prefix('*') {
+            Some(suffix) if suffix.contains('.') && !suffix.contains('*') => {
+                HostRule::Suffix(suffix.to_ascii_lowercase())
+            }
+            Some(_) => return Err(invalid("wildcard must look like *.example.com")),
+            None if host.is_empty() || host.contains('*') => {
+                return Err(invalid("wildcard is only allowed at the start of the host"));
+            }
+            None => HostRule::Exact(host.to_ascii_lowercase()),
+        };
+
+        Ok(OriginRule::Pattern { scheme: scheme.to_string(), host, port })
+    }
+
+    pub fn matches(&self, origin: &str) -> bool {
+        let (scheme, host, port) = match self {
+            OriginRule::Any => return true,
+            OriginRule::Pattern { scheme, host, port } => (scheme, host, port),
+        };
+
+        let Some((origin_scheme, rest)) = origin.split_once("://") else {
+            return false;
+        };
+        if origin_scheme != scheme {
+            return false;
+        }
+
+        let (origin_host, origin_port) = match rest.rsplit_once(':') {
+            Some((h, p)) => match p.parse::<u16>() {
+                Ok(p) => (h, Some(p)),
+                Err(_) => return false,
+            },
+            None => (rest, None),
+        };
+        let origin_host = origin_host.to_ascii_lowercase();
+
+        let host_ok = match host {
+            HostRule::Exact(h) => &origin_host == h,
+            HostRule::Suffix(suffix) => {
+                origin_host.ends_with(suffix.as_str()) && origin_host.len() > suffix.len()
+            }
+        };
+
+        let port_ok = match port {
+            PortRule::Default => origin_port.is_none(),
+            PortRule::Exact(p) => origin_port == Some(*p),
+            PortRule::Any => true,
+        };
+
+        host_ok && port_ok
+    }
+}
+
+// [origins หลัก + origins ของ environment ปัจจุบัน]
+pub fn origin_rules(config: &CorsConfig, app_env: &str) -> AppResult<Vec<OriginRule>> {
+    let extra = config.environments
+        .get(app_env)
+        .map(|e| e.origins.as_slice())
+        .unwrap_or_default();
+
+    config.origins
+        .iter()
+        .chain(extra)
+        .map(|o| OriginRule::parse(o))
+        .collect()
+}
+
+// [มี "*" = ตอบ Access-Control-Allow-Origin: * ตรง ๆ และไม่เปิด credentials
+//  (spec ห้ามใช้ * คู่กับ credentials จึงห้าม reflect origin ใดก็ได้พร้อม credentials ด้วย)]
+pub fn cors_layer(rules: Vec<OriginRule>) -> CorsLayer {
+    if rules.contains(&OriginRule::Any) {
+        return base_layer().allow_origin(AllowOrigin::any());
+    }
+
+    base_layer()
+        .allow_origin(AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin.to_str()
+                .map(|o| rules.iter().any(|rule| rule.matches(o)))
+                .unwrap_or(false)
+        }))
+        .allow_credentials(true)
+}
+
+fn base_layer() -> CorsLayer {
+    CorsLayer::new()
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE, Method::OPTIONS])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::ACCEPT,
+            HeaderName::from_static(SESSION_ID_HEADER),
+        ])
+        .expose_headers([HeaderName::from_static(REQUEST_ID_HEADER), header::CONTENT_DISPOSITION])
+}
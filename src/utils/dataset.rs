@@ -0,0 +1,121 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::controllers::chat::ChatMessage;
+use crate::utils::feedback::Rating;
+use crate::utils::hash::stable_hash;
+
+// -----------------------
+// PII redaction
+// เรียงจากแบบที่เจาะจงที่สุดก่อน (เลขยาวอย่างบัตร/บัตรประชาชน ก่อนเบอร์โทร)
+// -----------------------
+static PII_PATTERNS: Lazy<Vec<(Regex, &'static str)>> = Lazy::new(|| {
+    [
+        (r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}", "[EMAIL]"),
+        // [บัตรประชาชนไทย 13 หลัก เขียนติดกันหรือ 1-2345-67890-12-3]
+        (r"\b\d[\s-]?\d{4}[\s-]?\d{5}[\s-]?\d{2}[\s-]?\d\b", "[ID_NUMBER]"),
+        (r"\b\d(?:[\s-]?\d){12,18}\b", "[CARD]"),
+        // [ต้องหน้าตาเหมือนเบอร์โทร: ขึ้นต้น + หรือ 0, มีวงเล็บรหัสพื้นที่ หรือคั่นด้วย - แบบ 555-123-4567
+        //  เลขติดกันที่ไม่ขึ้นต้นด้วย 0 (เลขคำสั่งซื้อ ปี จำนวนเงิน) ไม่นับ]
+        (
+            r"(?:\+\d{1,3}[\s-]?\(?\d{1,4}\)?[\s-]?\d{3,4}[\s-]?\d{3,4}|\b0\d{1,2}[\s-]?\d{3}[\s-]?\d{3,4}|\(\d{2,4}\)[\s-]?\d{3}[\s-]?\d{3,4}|\b\d{3}-\d{3}-\d{4})\b",
+            "[PHONE]",
+        ),
+        (r"\b(?:\d{1,3}\.){3}\d{1,3}\b", "[IP]"),
+    ]
+    .into_iter()
+    .map(|(pattern, label)| (Regex::new(pattern).unwrap(), label))
+    .collect()
+});
+
+pub fn redact_pii(text: &str) -> String {
+    PII_PATTERNS
+        .iter()
+        .fold(text.to_string(), |text, (pattern, label)| pattern.replace_all(&text, *label).into_owned())
+}
+
+// -----------------------
+// Train / validation split
+// ตัดสินจาก hash ของ session_id ทำให้เรียก train กับ validation แยกกันแล้วไม่ซ้อนกัน
+// -----------------------
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Split {
+    #[default]
+    Train,
+    Validation,
+}
+
+pub fn split_for(session_id: &str, validation_ratio: f64) -> Split {
+    let bucket = (stable_hash(session_id) % 10_000) as f64 / 10_000.0;
+
+    if bucket < validation_ratio {
+        Split::Validation
+    } else {
+        Split::Train
+    }
+}
+
+// [up = ต้องมี 👍 อย่างน้อย 1 และไม่มี 👎, not_down = แค่ไม่มี 👎 (รวม session ที่ยังไม่มีใครให้คะแนน)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RatingFilter {
+    Up,
+    NotDown,
+}
+
+impl RatingFilter {
+    pub fn matches(&self, branch: &[ChatMessage]) -> bool {
+        let ratings: Vec<Rating> = branch.iter().filter_map(|m| m.feedback.as_ref()).map(|f| f.rating).collect();
+
+        if ratings.contains(&Rating::Down) {
+            return false;
+        }
+        match self {
+            RatingFilter::Up => ratings.contains(&Rating::Up),
+            RatingFilter::NotDown => true,
+        }
+    }
+}
+
+// -----------------------
+// OpenAI fine-tuning format: {"messages": [{"role", "content"}, ...]} ต่อบรรทัด
+// -----------------------
+#[derive(Serialize, Debug)]
+pub struct FineTuneMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FineTuneExample {
+    pub messages: Vec<FineTuneMessage>,
+}
+
+// [ใช้แค่ข้อความ (รูปไม่ส่งไป) ตัด user ท้ายที่ยังไม่มีคำตอบ ต้องมี assistant อย่างน้อย 1 ข้อความ]
+// [คำตอบที่ stream ขาดกลางทางไม่ครบ ใช้แค่ส่วนก่อนหน้านั้น]
+pub fn fine_tune_example(system_prompt: &str, branch: &[ChatMessage], redact: bool) -> Option<FineTuneExample> {
+    let mut turns: Vec<&ChatMessage> = branch
+        .iter()
+        .take_while(|m| !m.interrupted)
+        .filter(|m| (m.role == "user" || m.role == "assistant") && !m.content.trim().is_empty())
+        .collect();
+
+    while turns.last().is_some_and(|m| m.role != "assistant") {
+        turns.pop();
+    }
+    if turns.is_empty() {
+        return None;
+    }
+
+    let clean = |text: &str| if redact { redact_pii(text) } else { text.to_string() };
+
+    let mut messages = vec![FineTuneMessage { role: "system".into(), content: system_prompt.to_string() }];
+    messages.extend(turns.into_iter().map(|m| FineTuneMessage {
+        role: m.role.clone(),
+        content: clean(&m.content),
+    }));
+
+    Some(FineTuneExample { messages })
+}
@@ -1,28 +1,71 @@
-use reqwest::Client;
+use std::time::{Duration, Instant};
+
 use serde_json::json;
+use tracing::warn;
 
+use crate::app::error::AppError;
 use crate::app::result::AppResult;
+use crate::app::state::AppState;
+use crate::utils::metrics::{record_openai, record_tokens};
+use crate::utils::quota::QuotaBreach;
+
+pub async fn create_embedding(state: &AppState, user_id: &str, text: &str) -> AppResult<Vec<f32>> {
+    create_embeddings(state, user_id, &[text]).await?
+        .pop()
+        .ok_or_else(|| AppError::UpstreamError("Embedding response has no data".into()))
+}
+
+// [หลายข้อความใน request เดียว คืนตามลำดับของ texts, นับเข้าโควต้าของ user_id เหมือนแชท]
+pub async fn create_embeddings(state: &AppState, user_id: &str, texts: &[&str]) -> AppResult<Vec<Vec<f32>>> {
+    state.quota.check(user_id).map_err(QuotaBreach::into_error)?;
+
+    let started = Instant::now();
+    let result = request_embeddings(state, user_id, texts).await;
+    record_openai("embedding", started.elapsed(), result.is_ok());
+
+    if result.is_ok() {
+        if let Err(e) = state.quota.persist().await {
+            warn!(error = %e, "Failed to persist quota usage");
+        }
+    }
 
-pub async fn create_embedding(api_key: &str, text: &str) -> AppResult<Vec<f32>> {
-    let client = Client::new();
-    let res = client
-        .post("https://api.openai.com/v1/embeddings")
-        .bearer_auth(api_key)
-        .json(&json!({
-            "model": "text-embedding-3-small",
-            "input": text
-        }))
-        .send()
-        .await?
-        .json::<serde_json::Value>()
+    result
+}
+
+async fn request_embeddings(state: &AppState, user_id: &str, texts: &[&str]) -> AppResult<Vec<Vec<f32>>> {
+    let model = &state.config.openai.embedding_model;
+    let timeout = Duration::from_secs(state.config.resilience.embedding_timeout_secs);
+
+    let res: serde_json::Value = state.openai
+        .post_json("/embeddings", &json!({
+            "model": model,
+            "input": texts
+        }), timeout)
         .await?;
 
-    let embedding = res["data"][0]["embedding"]
+    let data = res["data"]
         .as_array()
-        .unwrap()
-        .iter()
-        .map(|v| v.as_f64().unwrap() as f32)
-        .collect::<Vec<f32>>();
+        .filter(|data| data.len() == texts.len())
+        .ok_or_else(|| AppError::UpstreamError("Embedding response has no data".into()))?;
+
+    let mut embeddings = vec![Vec::new(); texts.len()];
+    for (position, item) in data.iter().enumerate() {
+        let index = item["index"].as_u64().map(|i| i as usize).unwrap_or(position);
+        let slot = embeddings.get_mut(index)
+            .ok_or_else(|| AppError::UpstreamError(format!("Embedding index {index} out of range")))?;
+
+        *slot = item["embedding"]
+            .as_array()
+            .ok_or_else(|| AppError::UpstreamError("Embedding response has no data".into()))?
+            .iter()
+            .map(|v| v.as_f64().unwrap_or_default() as f32)
+            .collect();
+    }
+
+    if let Some(tokens) = res["usage"]["prompt_tokens"].as_u64() {
+        record_tokens(model, tokens, 0);
+        state.quota.record_embedding(user_id, tokens);
+    }
 
-    Ok(embedding)
+    Ok(embeddings)
 }
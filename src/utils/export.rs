@@ -0,0 +1,175 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::app::config::Config;
+use crate::app::persona::DEFAULT_PERSONA;
+use crate::controllers::chat::ChatMessage;
+
+// [ผู้ใช้ในมุมของราพีคือผู้บัญชาการ]
+const USER_LABEL: &str = "ผู้บัญชาการ";
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    #[serde(rename = "md")]
+    Markdown,
+    #[serde(rename = "html")]
+    Html,
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "jsonl")]
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+}
+
+// -----------------------
+// JSON export: ทั้ง log (ทุก branch) + metadata ใช้ import กลับได้
+// -----------------------
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SessionExport {
+    pub version: u32,
+    pub session_id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub persona: Option<String>,
+    #[serde(default)]
+    pub active_leaf: Option<String>,
+    pub exported_at: DateTime<Utc>,
+    pub messages: Vec<ChatMessage>,
+}
+
+// [ชื่อที่แสดงแทน role: user = ผู้บัญชาการ, assistant = ชื่อ persona]
+pub fn role_label(config: &Config, message: &ChatMessage, session_persona: Option<&str>) -> String {
+    match message.role.as_str() {
+        "user" => USER_LABEL.to_string(),
+        "assistant" => {
+            let id = message.persona.as_deref().or(session_persona).unwrap_or(DEFAULT_PERSONA);
+            config.personas.get(id).map(|p| p.name.clone()).unwrap_or_else(|| id.to_string())
+        }
+        role => role.to_string(),
+    }
+}
+
+fn format_time(timestamp: &DateTime<Utc>) -> String {
+    timestamp.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// -----------------------
+// Markdown / HTML แบ่งเป็นหัว + ทีละข้อความ + ท้าย เพื่อ stream ออกไปได้เรื่อย ๆ
+// images = URL หรือ data URL ที่เตรียมไว้แล้ว
+// -----------------------
+pub fn markdown_header(title: &str, exported_at: &DateTime<Utc>) -> String {
+    format!("# {}\n\n_ส่งออกเมื่อ {}_\n\n---\n\n", title, format_time(exported_at))
+}
+
+pub fn markdown_message(label: &str, message: &ChatMessage, images: &[String]) -> String {
+    let mut out = format!("**{}** · {}\n\n{}\n\n", label, format_time(&message.timestamp), message.content.trim_end());
+    for url in images {
+        out.push_str(&format!("![รูปที่แนบ]({url})\n\n"));
+    }
+    if let Some(feedback) = &message.feedback {
+        out.push_str(&format!("> {}", feedback.rating.symbol()));
+        if let Some(comment) = &feedback.comment {
+            out.push_str(&format!(" {}", comment.replace('\n', " ")));
+        }
+        out.push_str("\n\n");
+    }
+    out
+}
+
+pub fn html_header(title: &str, exported_at: &DateTime<Utc>) -> String {
+    let title = escape_html(title);
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="th">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; max-width: 760px; margin: 2rem auto; padding: 0 1rem; color: #222; }}
+.meta {{ color: #777; font-size: .85rem; }}
+.message {{ margin: 1rem 0; padding: .75rem 1rem; border-radius: 8px; background: #f4f4f6; page-break-inside: avoid; }}
+.message.user {{ background: #e8f0fe; }}
+.content {{ white-space: pre-wrap; margin-top: .4rem; }}
+.message img {{ max-width: 100%; margin-top: .5rem; border-radius: 4px; }}
+.feedback {{ color: #555; font-size: .85rem; margin-top: .4rem; }}
+@media print {{ body {{ margin: 0; }} .message {{ border: 1px solid #ddd; }} }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p class="meta">ส่งออกเมื่อ {exported}</p>
+"#,
+        exported = format_time(exported_at),
+    )
+}
+
+pub fn html_message(label: &str, message: &ChatMessage, images: &[String]) -> String {
+    let mut out = format!(
+        "<div class=\"message {}\">\n<strong>{}</strong> <span class=\"meta\">{}</span>\n<div class=\"content\">{}</div>\n",
+        escape_html(&message.role),
+        escape_html(label),
+        format_time(&message.timestamp),
+        escape_html(message.content.trim_end()),
+    );
+    for src in images {
+        out.push_str(&format!("<img src=\"{}\" alt=\"รูปที่แนบ\">\n", escape_html(src)));
+    }
+    if let Some(feedback) = &message.feedback {
+        out.push_str(&format!(
+            "<div class=\"feedback\">{} {}</div>\n",
+            feedback.rating.symbol(),
+            escape_html(feedback.comment.as_deref().unwrap_or_default()),
+        ));
+    }
+    out.push_str("</div>\n");
+    out
+}
+
+pub fn html_footer() -> &'static str {
+    "</body>\n</html>\n"
+}
+
+// [ชื่อไฟล์ใน Content-Disposition ใช้ได้แค่ตัวอักษรปลอดภัย]
+pub fn export_filename(session_id: &str, format: ExportFormat) -> String {
+    let safe: String = session_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    format!("rapi-chat-{}.{}", safe, format.extension())
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::app::config::Config;
+use crate::app::persona::DEFAULT_PERSONA;
+use crate::controllers::chat::ChatMessage;
+
+// [คำตอบเก่าก่อนมี prompt_version]
+pub const UNKNOWN_PROMPT_VERSION: &str = "unknown";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rating {
+    Up,
+    Down,
+}
+
+impl Rating {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Rating::Up => "👍",
+            Rating::Down => "👎",
+        }
+    }
+}
+
+// [เก็บไว้ใน ChatMessage ของคำตอบนั้นเลย regenerate แล้วจะย้ายไปอยู่กับ swipe]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Feedback {
+    pub rating: Rating,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+// -----------------------
+// รวม feedback ต่อ persona + prompt_version
+// -----------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct FeedbackStats {
+    pub persona: String,
+    pub prompt_version: String,
+    // [prompt_version นี้คือ system prompt ใน config ตอนนี้หรือไม่]
+    pub current: bool,
+    pub up: u64,
+    pub down: u64,
+    pub comments: u64,
+    // [up / (up + down)]
+    pub score: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct FeedbackAggregator {
+    counts: BTreeMap<(String, String), (u64, u64, u64)>,
+}
+
+impl FeedbackAggregator {
+    // [นับทั้งคำตอบปัจจุบันและ swipe เก่า เพราะ swipe เก่าคือผลของ prompt รุ่นก่อน]
+    pub fn add_messages(
+        &mut self,
+        messages: &[ChatMessage],
+        session_persona: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) {
+        let in_range = |feedback: &Feedback| {
+            since.is_none_or(|since| feedback.timestamp >= since) && until.is_none_or(|until| feedback.timestamp <= until)
+        };
+
+        for message in messages.iter().filter(|m| m.role == "assistant") {
+            let persona = message.persona.as_deref().or(session_persona).unwrap_or(DEFAULT_PERSONA);
+
+            if let Some(feedback) = message.feedback.as_ref().filter(|f| in_range(f)) {
+                self.add(persona, message.prompt_version.as_deref(), feedback);
+            }
+            for swipe in &message.swipes {
+                if let Some(feedback) = swipe.feedback.as_ref().filter(|f| in_range(f)) {
+                    self.add(swipe.persona.as_deref().unwrap_or(persona), swipe.prompt_version.as_deref(), feedback);
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, persona: &str, prompt_version: Option<&str>, feedback: &Feedback) {
+        let key = (persona.to_string(), prompt_version.unwrap_or(UNKNOWN_PROMPT_VERSION).to_string());
+        let (up, down, comments) = self.counts.entry(key).or_default();
+
+        match feedback.rating {
+            Rating::Up => *up += 1,
+            Rating::Down => *down += 1,
+        }
+        if feedback.comment.is_some() {
+            *comments += 1;
+        }
+    }
+
+    pub fn finish(self, config: &Config) -> Vec<FeedbackStats> {
+        self.counts
+            .into_iter()
+            .map(|((persona, prompt_version), (up, down, comments))| FeedbackStats {
+                current: config.personas.get(&persona).is_some_and(|p| p.prompt_version() == prompt_version),
+                score: up as f64 / (up + down).max(1) as f64,
+                persona,
+                prompt_version,
+                up,
+                down,
+                comments,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,6 @@
+// [FNV-1a 64 bit: ผลคงที่ข้ามเวอร์ชันของ Rust ต่างจาก DefaultHasher ใช้กับค่าที่ต้องบันทึกลงไฟล์]
+pub fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::controllers::chat::ChatMessage;
+use crate::utils::hash::stable_hash;
+
+// -----------------------
+// History tree
+// ข้อความทุก branch อยู่ในไฟล์เดียว เชื่อมกันด้วย parent_id, branch ที่ใช้อยู่ = ทางเดินจาก root ถึง active leaf
+// -----------------------
+
+// [log เก่าไม่มี id/parent_id ให้เติม id และต่อกันเป็นเส้นเดียวตามลำดับในไฟล์ คืน true ถ้ามีการแก้]
+// [id คำนวณจาก session + ตำแหน่งในไฟล์ อ่านกี่ครั้งก็ได้ id เดิม จึงไม่ต้องเขียนไฟล์กลับตอนอ่าน]
+pub fn normalize(session_id: &str, messages: &mut [ChatMessage]) -> bool {
+    let mut changed = false;
+
+    for (index, msg) in messages.iter_mut().enumerate().filter(|(_, m)| m.id.is_empty()) {
+        msg.id = legacy_message_id(session_id, index);
+        changed = true;
+    }
+
+    let legacy = messages.len() > 1 && messages.iter().all(|m| m.parent_id.is_none());
+    if legacy {
+        for i in 1..messages.len() {
+            messages[i].parent_id = Some(messages[i - 1].id.clone());
+        }
+        changed = true;
+    }
+
+    changed
+}
+
+fn legacy_message_id(session_id: &str, index: usize) -> String {
+    let key = format!("{session_id}:{index}");
+    Uuid::from_u64_pair(stable_hash(&key), stable_hash(&key.chars().rev().collect::<String>())).to_string()
+}
+
+// [leaf ที่ไม่มีแล้ว/ไม่ได้ตั้ง ให้ใช้ข้อความล่าสุดในไฟล์]
+pub fn branch_path(messages: &[ChatMessage], leaf_id: Option<&str>) -> Vec<ChatMessage> {
+    let by_id: HashMap<&str, &ChatMessage> = messages.iter().map(|m| (m.id.as_str(), m)).collect();
+
+    let mut current = leaf_id
+        .and_then(|id| by_id.get(id).copied())
+        .or_else(|| messages.last());
+
+    let mut path = Vec::new();
+    while let Some(msg) = current {
+        path.push(msg.clone());
+        current = msg.parent_id.as_deref().and_then(|p| by_id.get(p).copied());
+
+        // [กัน parent วนเป็นลูปจากไฟล์ที่ถูกแก้มือ]
+        if path.len() > messages.len() {
+            break;
+        }
+    }
+
+    path.reverse();
+    path
+}
+
+// [เลือก message ใดก็ได้ในกิ่ง แล้วเดินลงไปหาลูกล่าสุดจนสุดกิ่ง]
+pub fn resolve_leaf(messages: &[ChatMessage], id: &str) -> Option<String> {
+    let mut current = messages.iter().find(|m| m.id == id)?;
+
+    while let Some(child) = messages.iter()
+        .filter(|m| m.parent_id.as_deref() == Some(current.id.as_str()))
+        .max_by_key(|m| m.timestamp)
+    {
+        current = child;
+    }
+
+    Some(current.id.clone())
+}
+
+pub fn leaves(messages: &[ChatMessage]) -> Vec<&ChatMessage> {
+    messages.iter()
+        .filter(|m| !messages.iter().any(|c| c.parent_id.as_deref() == Some(m.id.as_str())))
+        .collect()
+}
@@ -50,6 +50,14 @@ pub fn get_filename_or_default(field: &Field) -> AppResult<String> {
     Ok(filename)
 }
 
+// [ไฟล์ใน images_dir เสิร์ฟผ่าน /api/images/{file}, URL ภายนอกส่งกลับตามเดิม]
+pub fn attachment_url(attachment: &str, images_dir: &str) -> String {
+    match attachment.strip_prefix(images_dir).and_then(|rest| rest.strip_prefix('/')) {
+        Some(filename) => format!("/api/images/{filename}"),
+        None => attachment.to_string(),
+    }
+}
+
 pub fn ensure_dir_once(dir_path: &str) -> AppResult<()> {
     let dirs = INIT_DIRS.get_or_init(DashSet::new);
 
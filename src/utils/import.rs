@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+use crate::controllers::chat::{new_message_id, ChatMessage, Swipe};
+use crate::utils::export::{SessionExport, EXPORT_VERSION};
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    #[default]
+    Auto,
+    // [JSON จาก /api/sessions/{id}/export?format=json]
+    Rapi,
+    #[serde(alias = "st")]
+    Sillytavern,
+    Chatgpt,
+}
+
+// [session_id ยังว่าง ให้ controller เป็นคนกำหนดตอนบันทึก]
+#[derive(Debug)]
+pub struct ImportedSession {
+    // [session_id เดิมจาก export ของเรา ใช้ต่อถ้ายังว่าง]
+    pub source_id: Option<String>,
+    pub title: Option<String>,
+    pub persona: Option<String>,
+    pub active_leaf: Option<String>,
+    pub messages: Vec<ChatMessage>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SkippedEntry {
+    pub entry: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedImport {
+    pub sessions: Vec<ImportedSession>,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+impl ParsedImport {
+    fn skip(&mut self, entry: impl Into<String>, reason: impl Into<String>) {
+        self.skipped.push(SkippedEntry { entry: entry.into(), reason: reason.into() });
+    }
+}
+
+pub fn parse_import(body: &str, format: ImportFormat) -> AppResult<ParsedImport> {
+    let format = match format {
+        ImportFormat::Auto => detect_format(body)?,
+        format => format,
+    };
+
+    let parsed = match format {
+        ImportFormat::Rapi => parse_rapi(body)?,
+        ImportFormat::Sillytavern => parse_sillytavern(body),
+        ImportFormat::Chatgpt => parse_chatgpt(body)?,
+        ImportFormat::Auto => unreachable!("format detected above"),
+    };
+
+    if parsed.sessions.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "Nothing to import ({} entries skipped)", parsed.skipped.len()
+        )));
+    }
+
+    Ok(parsed)
+}
+
+// [array / มี mapping = ChatGPT, มี version + messages = ของเรา, หลายบรรทัด = SillyTavern]
+fn detect_format(body: &str) -> AppResult<ImportFormat> {
+    let trimmed = body.trim_start();
+
+    if trimmed.starts_with('[') {
+        return Ok(ImportFormat::Chatgpt);
+    }
+
+    match serde_json::from_str::<Value>(trimmed) {
+        Ok(value) if value.get("mapping").is_some() => Ok(ImportFormat::Chatgpt),
+        Ok(value) if value.get("version").is_some() && value.get("messages").is_some() => Ok(ImportFormat::Rapi),
+        Ok(value) if value.get("mes").is_some() || value.get("user_name").is_some() => Ok(ImportFormat::Sillytavern),
+        Ok(_) => Err(AppError::BadRequest("Unrecognized import format".into())),
+        Err(_) if trimmed.starts_with('{') => Ok(ImportFormat::Sillytavern),
+        Err(e) => Err(AppError::InvalidJson(format!("Import is not JSON or JSONL: {e}"))),
+    }
+}
+
+fn is_chat_role(role: &str) -> bool {
+    role == "user" || role == "assistant"
+}
+
+// -----------------------
+// Export ของเราเอง: ได้ทุก branch, swipes, persona กลับมาครบ
+// id ใหม่ทั้งหมด เพราะ point id ใน Qdrant = message id (import ซ้ำจะได้ไม่ทับ session เดิม)
+// -----------------------
+fn parse_rapi(body: &str) -> AppResult<ParsedImport> {
+    let export: SessionExport = serde_json::from_str(body)
+        .map_err(|e| AppError::InvalidJson(format!("Invalid export file: {e}")))?;
+
+    if export.version > EXPORT_VERSION {
+        return Err(AppError::BadRequest(format!(
+            "Export version {} is newer than supported version {EXPORT_VERSION}", export.version
+        )));
+    }
+
+    let mut parsed = ParsedImport::default();
+    let mut ids: HashMap<String, String> = HashMap::new();
+    let mut messages = Vec::new();
+
+    for (i, message) in export.messages.into_iter().enumerate() {
+        let entry = format!("message {}", if message.id.is_empty() { i.to_string() } else { message.id.clone() });
+
+        if !is_chat_role(&message.role) {
+            parsed.skip(entry, format!("unsupported role '{}'", message.role));
+        } else if message.id.is_empty() || ids.contains_key(&message.id) {
+            parsed.skip(entry, "missing or duplicate id");
+        } else {
+            ids.insert(message.id.clone(), new_message_id());
+            messages.push(message);
+        }
+    }
+
+    for message in &mut messages {
+        message.id = ids[&message.id].clone();
+        message.parent_id = message.parent_id.as_ref().and_then(|p| ids.get(p).cloned());
+    }
+
+    if !messages.is_empty() {
+        parsed.sessions.push(ImportedSession {
+            source_id: Some(export.session_id),
+            title: export.title,
+            persona: export.persona,
+            active_leaf: export.active_leaf.and_then(|leaf| ids.get(&leaf).cloned()),
+            messages,
+        });
+    }
+
+    Ok(parsed)
+}
+
+// -----------------------
+// SillyTavern JSONL: บรรทัดแรกเป็น header (user_name, character_name) ที่เหลือบรรทัดละข้อความ
+// เป็นเส้นตรง, swipes ที่ไม่ได้เลือกเก็บเป็น swipes ของเรา
+// -----------------------
+fn parse_sillytavern(body: &str) -> ParsedImport {
+    let mut parsed = ParsedImport::default();
+    let mut messages: Vec<ChatMessage> = Vec::new();
+    let mut character = None;
+    let mut last_timestamp = None;
+
+    for (i, line) in body.lines().enumerate() {
+        let entry = format!("line {}", i + 1);
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let value: Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(e) => {
+                parsed.skip(entry, format!("invalid JSON: {e}"));
+                continue;
+            }
+        };
+
+        let Some(content) = value.get("mes").and_then(Value::as_str) else {
+            if value.get("user_name").is_some() || value.get("chat_metadata").is_some() {
+                character = value.get("character_name").and_then(Value::as_str).map(str::to_string);
+            } else {
+                parsed.skip(entry, "missing 'mes'");
+            }
+            continue;
+        };
+
+        if value.get("is_system").and_then(Value::as_bool).unwrap_or(false) {
+            parsed.skip(entry, "system message");
+            continue;
+        }
+        if content.trim().is_empty() {
+            parsed.skip(entry, "empty message");
+            continue;
+        }
+
+        let role = if value.get("is_user").and_then(Value::as_bool).unwrap_or(false) { "user" } else { "assistant" };
+        let timestamp = value.get("send_date")
+            .and_then(parse_loose_timestamp)
+            .or(last_timestamp)
+            .unwrap_or_else(Utc::now);
+        last_timestamp = Some(timestamp);
+
+        let swipes = value.get("swipes")
+            .and_then(Value::as_array)
+            .map(|swipes| swipes.iter()
+                .filter_map(Value::as_str)
+                .filter(|s| *s != content && !s.trim().is_empty())
+                .map(|s| Swipe { content: s.to_string(), timestamp, persona: None, prompt_version: None, feedback: None })
+                .collect())
+            .unwrap_or_default();
+
+        let message = ChatMessage {
+            parent_id: messages.last().map(|m| m.id.clone()),
+            timestamp,
+            swipes,
+            ..ChatMessage::new(&new_message_id(), "", role, content)
+        };
+        messages.push(message);
+    }
+
+    if !messages.is_empty() {
+        parsed.sessions.push(ImportedSession {
+            source_id: None,
+            title: character.map(|name| format!("แชทกับ {name}")),
+            persona: None,
+            active_leaf: None,
+            messages,
+        });
+    }
+
+    parsed
+}
+
+// [SillyTavern เคยเก็บ send_date หลายแบบ: RFC 3339, epoch ms, "June 3, 2024 5:04pm"]
+pub fn parse_loose_timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    if let Some(n) = value.as_f64() {
+        let secs = if n > 1e11 { n / 1000.0 } else { n };
+        return Utc.timestamp_opt(secs as i64, 0).single();
+    }
+
+    let text = value.as_str()?.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    ["%B %d, %Y %I:%M%p", "%B %d, %Y %I:%M %p", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d@%Hh%Mm%Ss"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(text, fmt).ok())
+        .map(|naive| naive.and_utc())
+}
+
+// -----------------------
+// ChatGPT conversations.json: array ของ conversation แต่ละอันเป็น tree ใน "mapping"
+// เก็บ tree ไว้ทั้งหมด (แก้ข้อความใน ChatGPT = branch ของเรา), current_node = branch ที่ใช้อยู่
+// -----------------------
+#[derive(Deserialize, Debug)]
+struct ChatGptConversation {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    create_time: Option<f64>,
+    mapping: HashMap<String, ChatGptNode>,
+    #[serde(default)]
+    current_node: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatGptNode {
+    #[serde(default)]
+    message: Option<ChatGptMessage>,
+    #[serde(default)]
+    parent: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatGptMessage {
+    author: ChatGptAuthor,
+    #[serde(default)]
+    content: Value,
+    #[serde(default)]
+    create_time: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatGptAuthor {
+    role: String,
+}
+
+fn parse_chatgpt(body: &str) -> AppResult<ParsedImport> {
+    let value: Value = serde_json::from_str(body)
+        .map_err(|e| AppError::InvalidJson(format!("Invalid conversations.json: {e}")))?;
+
+    let conversations = match value {
+        Value::Array(items) => items,
+        single => vec![single],
+    };
+
+    let mut parsed = ParsedImport::default();
+
+    for (i, raw) in conversations.into_iter().enumerate() {
+        let entry = format!("conversation {}", i + 1);
+
+        match serde_json::from_value::<ChatGptConversation>(raw) {
+            Ok(conversation) => {
+                let entry = match &conversation.title {
+                    Some(title) => format!("{entry} ({title})"),
+                    None => entry,
+                };
+                match chatgpt_session(conversation, &entry, &mut parsed) {
+                    Some(session) => parsed.sessions.push(session),
+                    None => parsed.skip(entry, "no user or assistant messages"),
+                }
+            }
+            Err(e) => parsed.skip(entry, format!("invalid conversation: {e}")),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn chatgpt_session(conversation: ChatGptConversation, entry: &str, parsed: &mut ParsedImport) -> Option<ImportedSession> {
+    let fallback_time = conversation.create_time
+        .and_then(|t| Utc.timestamp_opt(t as i64, 0).single())
+        .unwrap_or_else(Utc::now);
+
+    // [node ที่เก็บได้: role user/assistant ที่มีข้อความ (node ว่าง/system ที่ซ่อนไว้ข้ามเงียบ ๆ)]
+    let mut kept: HashMap<&str, ChatMessage> = HashMap::new();
+    for (node_id, node) in &conversation.mapping {
+        let Some(message) = &node.message else { continue };
+        let text = chatgpt_text(&message.content);
+        if text.trim().is_empty() {
+            continue;
+        }
+        if !is_chat_role(&message.author.role) {
+            parsed.skip(format!("{entry} node {node_id}"), format!("unsupported role '{}'", message.author.role));
+            continue;
+        }
+
+        let timestamp = message.create_time
+            .and_then(|t| Utc.timestamp_opt(t as i64, 0).single())
+            .unwrap_or(fallback_time);
+
+        kept.insert(node_id, ChatMessage {
+            timestamp,
+            ..ChatMessage::new(&new_message_id(), "", &message.author.role, &text)
+        });
+    }
+
+    if kept.is_empty() {
+        return None;
+    }
+
+    // [parent = บรรพบุรุษใกล้สุดที่เก็บไว้ ข้าม node ที่ถูกตัดทิ้ง]
+    let nearest_kept = |start: Option<&String>| -> Option<String> {
+        let mut current = start;
+        for _ in 0..conversation.mapping.len() {
+            let id = current?;
+            if let Some(message) = kept.get(id.as_str()) {
+                return Some(message.id.clone());
+            }
+            current = conversation.mapping.get(id)?.parent.as_ref();
+        }
+        None
+    };
+
+    let parents: Vec<(&str, Option<String>)> = kept.keys()
+        .map(|node_id| (*node_id, nearest_kept(conversation.mapping[*node_id].parent.as_ref())))
+        .collect();
+    let active_leaf = nearest_kept(conversation.current_node.as_ref());
+
+    let mut messages: Vec<ChatMessage> = parents.into_iter()
+        .map(|(node_id, parent_id)| ChatMessage { parent_id, ..kept[node_id].clone() })
+        .collect();
+    messages.sort_by_key(|m| m.timestamp);
+
+    Some(ImportedSession {
+        source_id: None,
+        title: conversation.title.filter(|t| !t.trim().is_empty()),
+        persona: None,
+        active_leaf,
+        messages,
+    })
+}
+
+// [content.parts เป็น string (รูปเป็น object ข้ามไป), บาง content_type ใช้ field "text"]
+fn chatgpt_text(content: &Value) -> String {
+    if let Some(parts) = content.get("parts").and_then(Value::as_array) {
+        return parts.iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    content.get("text").and_then(Value::as_str).unwrap_or_default().to_string()
+}
@@ -0,0 +1,156 @@
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests_total: IntCounterVec,
+    pub http_request_duration_seconds: HistogramVec,
+    pub openai_request_duration_seconds: HistogramVec,
+    pub openai_failures_total: IntCounterVec,
+    pub qdrant_request_duration_seconds: HistogramVec,
+    pub qdrant_failures_total: IntCounterVec,
+    pub background_jobs_total: IntCounterVec,
+    pub summaries_generated_total: IntCounter,
+    pub tokens_consumed_total: IntCounterVec,
+}
+
+// [upstream ช้ากว่า HTTP ปกติ เลยขยาย bucket ไปถึง 60s]
+const UPSTREAM_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0];
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(|| {
+    let registry = Registry::new_custom(Some("rapi".into()), None)
+        .expect("valid metrics prefix");
+
+    let http_requests_total = IntCounterVec::new(
+        Opts::new("http_requests_total", "HTTP requests by route and status"),
+        &["method", "route", "status"],
+    ).unwrap();
+    let http_request_duration_seconds = HistogramVec::new(
+        HistogramOpts::new("http_request_duration_seconds", "HTTP request latency")
+            .buckets(UPSTREAM_BUCKETS.to_vec()),
+        &["method", "route"],
+    ).unwrap();
+    let openai_request_duration_seconds = HistogramVec::new(
+        HistogramOpts::new("openai_request_duration_seconds", "OpenAI request latency by kind")
+            .buckets(UPSTREAM_BUCKETS.to_vec()),
+        &["kind", "outcome"],
+    ).unwrap();
+    let openai_failures_total = IntCounterVec::new(
+        Opts::new("openai_failures_total", "Failed OpenAI requests by kind"),
+        &["kind"],
+    ).unwrap();
+    let qdrant_request_duration_seconds = HistogramVec::new(
+        HistogramOpts::new("qdrant_request_duration_seconds", "Qdrant request latency by operation"),
+        &["op", "outcome"],
+    ).unwrap();
+    let qdrant_failures_total = IntCounterVec::new(
+        Opts::new("qdrant_failures_total", "Failed Qdrant requests by operation"),
+        &["op"],
+    ).unwrap();
+    let background_jobs_total = IntCounterVec::new(
+        Opts::new("background_jobs_total", "Background job runs by outcome"),
+        &["job", "outcome"],
+    ).unwrap();
+    let summaries_generated_total = IntCounter::new(
+        "summaries_generated_total", "Conversation summaries generated",
+    ).unwrap();
+    let tokens_consumed_total = IntCounterVec::new(
+        Opts::new("tokens_consumed_total", "Tokens consumed by model and kind"),
+        &["model", "kind"],
+    ).unwrap();
+
+    registry.register(Box::new(http_requests_total.clone())).unwrap();
+    registry.register(Box::new(http_request_duration_seconds.clone())).unwrap();
+    registry.register(Box::new(openai_request_duration_seconds.clone())).unwrap();
+    registry.register(Box::new(openai_failures_total.clone())).unwrap();
+    registry.register(Box::new(qdrant_request_duration_seconds.clone())).unwrap();
+    registry.register(Box::new(qdrant_failures_total.clone())).unwrap();
+    registry.register(Box::new(background_jobs_total.clone())).unwrap();
+    registry.register(Box::new(summaries_generated_total.clone())).unwrap();
+    registry.register(Box::new(tokens_consumed_total.clone())).unwrap();
+
+    Metrics {
+        registry,
+        http_requests_total,
+        http_request_duration_seconds,
+        openai_request_duration_seconds,
+        openai_failures_total,
+        qdrant_request_duration_seconds,
+        qdrant_failures_total,
+        background_jobs_total,
+        summaries_generated_total,
+        tokens_consumed_total,
+    }
+});
+
+fn outcome(ok: bool) -> &'static str {
+    if ok { "ok" } else { "error" }
+}
+
+pub fn record_openai(kind: &str, elapsed: Duration, ok: bool) {
+    METRICS.openai_request_duration_seconds
+        .with_label_values(&[kind, outcome(ok)])
+        .observe(elapsed.as_secs_f64());
+    if !ok {
+        METRICS.openai_failures_total.with_label_values(&[kind]).inc();
+    }
+}
+
+pub fn record_qdrant(op: &str, elapsed: Duration, ok: bool) {
+    METRICS.qdrant_request_duration_seconds
+        .with_label_values(&[op, outcome(ok)])
+        .observe(elapsed.as_secs_f64());
+    if !ok {
+        METRICS.qdrant_failures_total.with_label_values(&[op]).inc();
+    }
+}
+
+pub fn record_tokens(model: &str, prompt_tokens: u64, completion_tokens: u64) {
+    METRICS.tokens_consumed_total.with_label_values(&[model, "prompt"]).inc_by(prompt_tokens);
+    METRICS.tokens_consumed_total.with_label_values(&[model, "completion"]).inc_by(completion_tokens);
+}
+
+pub fn record_background_job(job: &str, ok: bool) {
+    METRICS.background_jobs_total.with_label_values(&[job, outcome(ok)]).inc();
+}
+
+// [ใช้กับ route_layer เพื่อให้ได้ MatchedPath แทน path จริง กัน label ระเบิด]
+pub async fn track_http(req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let route = req.extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".into());
+
+    let res = next.run(req).await;
+
+    METRICS.http_requests_total
+        .with_label_values(&[&method, &route, res.status().as_str()])
+        .inc();
+    METRICS.http_request_duration_seconds
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+
+    res
+}
+
+pub fn render() -> AppResult<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .map_err(|e| AppError::InternalError(format!("Metrics encode error: {e}")))?;
+
+    String::from_utf8(buffer)
+        .map_err(|e| AppError::InternalError(format!("Metrics encode error: {e}")))
+}
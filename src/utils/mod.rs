@@ -2,4 +2,24 @@ pub mod image;
 pub mod embedding;
 pub mod qdrant;
 pub mod summarizer;
-pub mod log;
\ No newline at end of file
+pub mod log;
+pub mod quota;
+pub mod cors;
+pub mod telemetry;
+pub mod metrics;
+pub mod openai;
+pub mod resilience;
+pub mod completion;
+pub mod model_router;
+pub mod chat_input;
+pub mod history;
+pub mod session_meta;
+pub mod session_lock;
+pub mod session_title;
+pub mod export;
+pub mod import;
+pub mod dataset;
+pub mod hash;
+pub mod feedback;
+pub mod search_index;
+pub mod sparse;
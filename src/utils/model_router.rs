@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::app::config::{Config, GenerationLimits};
+use crate::app::error::AppError;
+use crate::app::persona::{ModelTarget, PersonaConfig};
+use crate::app::result::AppResult;
+use crate::utils::completion::GenerationParams;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Task {
+    Chat,
+    VisionChat,
+    Summarization,
+    FactExtraction,
+    TitleGeneration,
+    Moderation,
+}
+
+impl Task {
+    pub const ALL: [Task; 6] = [
+        Task::Chat,
+        Task::VisionChat,
+        Task::Summarization,
+        Task::FactExtraction,
+        Task::TitleGeneration,
+        Task::Moderation,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Task::Chat => "chat",
+            Task::VisionChat => "vision_chat",
+            Task::Summarization => "summarization",
+            Task::FactExtraction => "fact_extraction",
+            Task::TitleGeneration => "title_generation",
+            Task::Moderation => "moderation",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskRoute {
+    // [ลองตามลำดับเหมือน models ของ persona]
+    pub models: Vec<ModelTarget>,
+    pub params: GenerationParams,
+}
+
+// -----------------------
+// Model router
+// งานแต่ละแบบใช้โมเดลของตัวเองได้ เช่นแชทใช้ gpt-4o แต่สรุปใช้ gpt-4o-mini
+// ไม่ได้ตั้งไว้ = ใช้โมเดลชุดเดียวกับ chat (base_url อาจเป็น provider ที่ไม่มีโมเดลของ OpenAI)
+// -----------------------
+#[derive(Debug, Clone)]
+pub struct ModelRouter {
+    routes: HashMap<Task, TaskRoute>,
+    limits: GenerationLimits,
+}
+
+impl ModelRouter {
+    pub fn new(config: &Config) -> Self {
+        let mut routes = HashMap::new();
+
+        for task in Task::ALL {
+            // [ใส่แค่ params ไม่ใส่ models ก็ได้ จะใช้โมเดล default ของงานนั้น]
+            let mut route = config.routing.get(&task).cloned().unwrap_or_default();
+            if route.models.is_empty() {
+                route.models = match task {
+                    Task::Chat => vec![ModelTarget {
+                        provider: "openai".into(),
+                        model: config.openai.model.clone(),
+                        vision: true,
+                    }],
+                    // [Chat มาก่อนใน ALL]
+                    _ => routes.get(&Task::Chat).map(|r: &TaskRoute| r.models.clone()).unwrap_or_default(),
+                };
+            }
+            route.params = route.params.clamp(&config.generation);
+            routes.insert(task, route);
+        }
+
+        Self { routes, limits: config.generation.clone() }
+    }
+
+    pub fn route(&self, task: Task) -> &TaskRoute {
+        &self.routes[&task]
+    }
+
+    // [persona มี models ของตัวเองให้ใช้ก่อน ไม่งั้นใช้ chat/vision_chat route]
+    // [params: route -> persona -> request แล้วบีบตาม [generation]]
+    pub fn chat_route(
+        &self,
+        persona: &PersonaConfig,
+        has_image: bool,
+        params: &GenerationParams,
+    ) -> AppResult<(Task, TaskRoute)> {
+        if let Some(name) = params.non_finite() {
+            return Err(AppError::BadRequest(format!("{name} must be a finite number")));
+        }
+
+        let task = if has_image { Task::VisionChat } else { Task::Chat };
+        let mut route = self.route(task).clone();
+
+        route.params = route.params
+            .merge(&persona.params)
+            .merge(params)
+            .clamp(&self.limits);
+
+        if !persona.models.is_empty() {
+            route.models = persona.models.clone();
+        }
+
+        if has_image {
+            route.models.retain(|t| t.vision);
+            if route.models.is_empty() {
+                return Err(AppError::BadRequest("No vision-capable model is configured for this persona".into()));
+            }
+        }
+
+        Ok((task, route))
+    }
+}
@@ -0,0 +1,223 @@
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::app::config::ResilienceConfig;
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+use crate::utils::resilience::{should_retry, CircuitBreaker, RetryPolicy};
+
+#[derive(Deserialize, Debug)]
+struct OpenAiErrorResponse {
+    error: OpenAiErrorBody,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAiErrorBody {
+    message: String,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    code: Option<String>,
+}
+
+// [แปลง error ของ provider เป็น AppError ที่บอกได้ว่าฝั่งไหนผิด]
+pub fn classify_error(status: StatusCode, body: &str) -> AppError {
+    let (message, kind, code) = match serde_json::from_str::<OpenAiErrorResponse>(body) {
+        Ok(res) => (res.error.message, res.error.kind.unwrap_or_default(), res.error.code.unwrap_or_default()),
+        Err(_) => (format!("Provider returned {status}"), String::new(), String::new()),
+    };
+
+    let is = |needle: &str| code == needle || kind == needle;
+
+    match status {
+        _ if is("context_length_exceeded") || is("string_above_max_length") => {
+            AppError::ContextLengthExceeded(message)
+        }
+        _ if is("content_policy_violation") || is("content_filter") => AppError::ContentPolicy(message),
+        // [เครดิตหมด ไม่ใช่ rate limit ชั่วคราว รอไปก็ไม่หาย]
+        StatusCode::TOO_MANY_REQUESTS if is("insufficient_quota") => AppError::UpstreamError(message),
+        StatusCode::TOO_MANY_REQUESTS => AppError::UpstreamRateLimited(message),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => AppError::UpstreamAuth(message),
+        // [408 = provider รอ request นานเกินไป ลองใหม่ได้เหมือน 5xx]
+        StatusCode::REQUEST_TIMEOUT => AppError::UpstreamUnavailable(message),
+        // [4xx อื่น ๆ (400, 404, 422, ...) = request ของเราผิด ส่งซ้ำก็ได้ผลเดิม]
+        s if s.is_client_error() => AppError::UpstreamRejected(message),
+        s if s.is_server_error() => AppError::UpstreamUnavailable(message),
+        _ => AppError::UpstreamError(message),
+    }
+}
+
+// [Retry-After เป็นได้ทั้งจำนวนวินาที หรือ HTTP-date (Sun, 06 Nov 1994 08:49:37 GMT) เวลาที่ผ่านไปแล้ว = ลองได้เลย]
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+pub async fn read_json<T: DeserializeOwned>(res: reqwest::Response) -> AppResult<T> {
+    let body = check_status(res).await?.text().await?;
+
+    serde_json::from_str(&body)
+        .map_err(|e| AppError::UpstreamError(format!("Unexpected response format: {e}")))
+}
+
+pub async fn check_status(res: reqwest::Response) -> AppResult<reqwest::Response> {
+    let status = res.status();
+
+    if !status.is_success() {
+        let body = res.text().await?;
+        return Err(classify_error(status, &body));
+    }
+
+    Ok(res)
+}
+
+// -----------------------
+// OpenAI-compatible client
+// timeout ต่อ call + retry แบบ jitter (เคารพ Retry-After) + circuit breaker
+// stream_http ไม่มี timeout รวม ใช้กับ post_stream เท่านั้น
+// -----------------------
+pub struct OpenAiClient {
+    pub name: String,
+    http: reqwest::Client,
+    stream_http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    policy: RetryPolicy,
+    breaker: CircuitBreaker,
+}
+
+impl OpenAiClient {
+    pub fn new(
+        name: &str,
+        http: reqwest::Client,
+        stream_http: reqwest::Client,
+        base_url: &str,
+        api_key: &str,
+        resilience: &ResilienceConfig,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            http,
+            stream_http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            policy: RetryPolicy::from_config(resilience),
+            breaker: CircuitBreaker::from_config(resilience),
+        }
+    }
+
+    pub fn breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    // [ollama/local ไม่ต้องใช้ key ก็ไม่ต้องส่ง Authorization]
+    fn request(&self, http: &reqwest::Client, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let req = http.request(method, format!("{}{}", self.base_url, path));
+
+        if self.api_key.is_empty() {
+            req
+        } else {
+            req.bearer_auth(&self.api_key)
+        }
+    }
+
+    pub async fn get(&self, path: &str, timeout: Duration) -> AppResult<reqwest::Response> {
+        let res = self
+            .request(&self.http, reqwest::Method::GET, path)
+            .timeout(timeout)
+            .send()
+            .await?;
+
+        Ok(res)
+    }
+
+    pub async fn post_json<B, T>(&self, path: &str, body: &B, timeout: Duration) -> AppResult<T>
+    where
+        B: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        self.execute(path, body, timeout, false, read_json).await
+    }
+
+    // [สำหรับ stream: คืน response ทันทีที่ได้ 2xx แล้วให้คนเรียกอ่าน body ต่อเอง]
+    // [timeout = รอถึง response header เท่านั้น ระหว่างอ่าน body ให้คนเรียกจับช่วงเงียบเอง (ดู CompletionStream)]
+    pub async fn post_stream<B>(&self, path: &str, body: &B, timeout: Duration) -> AppResult<reqwest::Response>
+    where
+        B: Serialize + ?Sized,
+    {
+        self.execute(path, body, timeout, true, check_status).await
+    }
+
+    async fn execute<B, T, F, Fut>(&self, path: &str, body: &B, timeout: Duration, stream: bool, finish: F) -> AppResult<T>
+    where
+        B: Serialize + ?Sized,
+        F: Fn(reqwest::Response) -> Fut,
+        Fut: Future<Output = AppResult<T>>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            self.breaker.acquire(&self.name)?;
+
+            let (result, retry_after) = match self.send_once(path, body, timeout, stream).await {
+                Ok((res, retry_after)) => (finish(res).await, retry_after),
+                Err(e) => (Err(e), None),
+            };
+            self.breaker.record(&self.name, &result);
+
+            match result {
+                Err(e) if should_retry(&e) && attempt < self.policy.max_retries => {
+                    let delay = self.policy.delay(attempt, retry_after);
+                    tracing::warn!(
+                        upstream = %self.name, path, attempt, delay_ms = delay.as_millis() as u64, error = %e,
+                        "Retrying upstream call"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send_once<B>(
+        &self,
+        path: &str,
+        body: &B,
+        timeout: Duration,
+        stream: bool,
+    ) -> AppResult<(reqwest::Response, Option<Duration>)>
+    where
+        B: Serialize + ?Sized,
+    {
+        let res = if stream {
+            let send = self.request(&self.stream_http, reqwest::Method::POST, path).json(body).send();
+            tokio::time::timeout(timeout, send)
+                .await
+                .map_err(|_| AppError::UpstreamUnavailable(format!("No response within {}s", timeout.as_secs_f32())))??
+        } else {
+            self.request(&self.http, reqwest::Method::POST, path)
+                .timeout(timeout)
+                .json(body)
+                .send()
+                .await?
+        };
+
+        let retry_after = res.headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| parse_retry_after(v, Utc::now()));
+
+        Ok((res, retry_after))
+    }
+}
@@ -1,112 +1,305 @@
-use qdrant_client::qdrant::{CreateCollection, Distance, PointId, PointStruct, UpsertPoints, VectorParams, Vectors, VectorsConfig};
+use qdrant_client::qdrant::{CreateCollection, DeletePoints, Distance, NamedVectors, PointId, PointStruct, UpsertPoints, Vector, VectorParams, Vectors, VectorsConfig};
 use qdrant_client::Qdrant;
-use uuid::Uuid;
+use crate::app::config::QdrantConfig;
 use crate::app::error::AppError;
 use crate::app::result::AppResult;
+use crate::utils::metrics::record_qdrant;
+use std::time::Instant;
 use crate::controllers::chat::ChatMessage;
 use serde_json::json;
 use qdrant_client::qdrant::{SearchPoints, Filter, Condition};
+use qdrant_client::qdrant::point_id::PointIdOptions;
+use qdrant_client::qdrant::{
+    Fusion, Modifier, PrefetchQueryBuilder, Query, QueryPointsBuilder, ScoredPoint, SparseVectorConfig, SparseVectorParams,
+};
+use crate::utils::sparse::{document_vector, query_vector};
 
 
 use qdrant_client::qdrant::{
     Datatype, HnswConfigDiff
 };
 
-pub async fn ensure_collection(client: &Qdrant) -> AppResult<()> {
-    let exists = client.collection_exists("chat_memory")
+// [ชื่อ named vector ของ sparse (BM25) ส่วน dense ยังเป็น vector หลักที่ไม่มีชื่อเหมือนเดิม]
+pub const SPARSE_VECTOR: &str = "bm25";
+// [ดึง candidate จากแต่ละฝั่งมากกว่า limit ก่อนนำมา fuse]
+const PREFETCH_MULTIPLIER: u64 = 4;
+
+// [คืนค่าว่าใช้ hybrid ได้หรือไม่: collection เก่าที่สร้างก่อนมี sparse vector เพิ่ม vector ทีหลังไม่ได้ จะใช้ dense อย่างเดียว]
+pub async fn ensure_collection(client: &Qdrant, config: &QdrantConfig) -> AppResult<bool> {
+    let exists = client.collection_exists(&config.collection)
         .await
         .map_err(|e| AppError::QdrantError(e.to_string()))?;
 
-    if !exists {
-        client.create_collection(CreateCollection {
-            collection_name: "chat_memory".to_string(),
-            vectors_config: Some(VectorsConfig {
-                config: Some(qdrant_client::qdrant::vectors_config::Config::Params(
-                    VectorParams {
-                        size: 1536, // [ขนาด embedding model]
-                        distance: Distance::Cosine.into(),
-                        datatype: Some(Datatype::Float32 as i32),
-                        hnsw_config: Some(HnswConfigDiff::default()),
-                        multivector_config: None,
-                        on_disk: None,
-                        quantization_config: None,
-                    }
-                )),
-            }),
-            ..Default::default()
-        }).await.map_err(|e| AppError::QdrantError(e.to_string()))?;
+    if exists {
+        if !config.hybrid {
+            return Ok(false);
+        }
 
-        println!("Collection 'chat_memory' created");
+        let info = client.collection_info(&config.collection)
+            .await
+            .map_err(|e| AppError::QdrantError(e.to_string()))?;
+        let has_sparse = info.result
+            .and_then(|r| r.config)
+            .and_then(|c| c.params)
+            .and_then(|p| p.sparse_vectors_config)
+            .is_some_and(|s| s.map.contains_key(SPARSE_VECTOR));
+
+        if !has_sparse {
+            tracing::warn!(
+                collection = %config.collection,
+                "Collection has no sparse vector, hybrid retrieval disabled (recreate the collection to enable it)"
+            );
+        }
+        return Ok(has_sparse);
     }
 
-    Ok(())
+    client.create_collection(CreateCollection {
+        collection_name: config.collection.clone(),
+        vectors_config: Some(VectorsConfig {
+            config: Some(qdrant_client::qdrant::vectors_config::Config::Params(
+                VectorParams {
+                    size: config.vector_size, // [ขนาด embedding model]
+                    distance: Distance::Cosine.into(),
+                    datatype: Some(Datatype::Float32 as i32),
+                    hnsw_config: Some(HnswConfigDiff::default()),
+                    multivector_config: None,
+                    on_disk: None,
+                    quantization_config: None,
+                }
+            )),
+        }),
+        sparse_vectors_config: config.hybrid.then(|| SparseVectorConfig {
+            map: [(SPARSE_VECTOR.to_string(), SparseVectorParams {
+                index: None,
+                modifier: Some(Modifier::Idf as i32),
+            })].into(),
+        }),
+        ..Default::default()
+    }).await.map_err(|e| AppError::QdrantError(e.to_string()))?;
+
+    tracing::info!(collection = %config.collection, hybrid = config.hybrid, "Collection created");
+
+    Ok(config.hybrid)
+}
+
+fn point_vectors(config: &QdrantConfig, content: &str, embedding: Vec<f32>) -> Vectors {
+    if !config.hybrid {
+        return Vectors::from(embedding);
+    }
+
+    Vectors::from(NamedVectors::from(vec![
+        (String::new(), Vector::from(embedding)),
+        (SPARSE_VECTOR.to_string(), Vector::from(document_vector(content))),
+    ]))
 }
 
 
+// [point id = message id ทำให้ upsert ซ้ำด้วย id เดิมเป็นการแทนที่]
 pub async fn store_message_to_qdrant(
     client: &Qdrant,
-    session_id: &str,
-    role: &str,
-    content: &str,
+    config: &QdrantConfig,
+    message: &ChatMessage,
     embedding: Vec<f32>,
-    timestamp: i64,
 ) -> AppResult<()> {
-    let point = PointStruct::new(
-        PointId::from(Uuid::new_v4().to_string()),
-        Vectors::from(embedding),
-        json!({
-            "session_id": session_id,
-            "role": role,
-            "content": content,
-            "timestamp": timestamp
-        }).as_object().unwrap().clone(),
-    );
+    store_messages_to_qdrant(client, config, vec![(message, embedding)]).await
+}
+
+// [upsert หลายข้อความใน request เดียว ใช้ตอน import]
+pub async fn store_messages_to_qdrant(
+    client: &Qdrant,
+    config: &QdrantConfig,
+    messages: Vec<(&ChatMessage, Vec<f32>)>,
+) -> AppResult<()> {
+    let points = messages
+        .into_iter()
+        .map(|(message, embedding)| {
+            let mut payload = json!({
+                "session_id": message.session_id,
+                "role": message.role,
+                "content": message.content,
+                "timestamp": message.timestamp.timestamp()
+            }).as_object().unwrap().clone();
+            // [summary: parent_id = ข้อความสุดท้ายของ branch ที่ถูกสรุป ใช้กรองตาม branch]
+            if let Some(parent_id) = &message.parent_id {
+                payload.insert("parent_id".into(), json!(parent_id));
+            }
+
+            PointStruct::new(
+                PointId::from(message.id.clone()),
+                point_vectors(config, &message.content, embedding),
+                payload,
+            )
+        })
+        .collect();
 
     let upsert = UpsertPoints {
-        collection_name: "chat_memory".to_string(),
+        collection_name: config.collection.clone(),
         wait: Some(true),
-        points: vec![point],
+        points,
         ordering: None,
         shard_key_selector: None,
     };
 
-    client.upsert_points(upsert)
-        .await
-        .map_err(|e| AppError::InternalError(format!("Qdrant upsert error: {}", e)))?;
+    let started = Instant::now();
+    let result = client.upsert_points(upsert).await;
+    record_qdrant("upsert", started.elapsed(), result.is_ok());
+    result.map_err(|e| AppError::InternalError(format!("Qdrant upsert error: {}", e)))?;
+
+    Ok(())
+}
+
+// [ลบทุก point ของ session รวมถึง summary]
+pub async fn delete_session_points(client: &Qdrant, collection: &str, session_id: &str) -> AppResult<()> {
+    let started = Instant::now();
+    let result = client.delete_points(DeletePoints {
+        collection_name: collection.to_string(),
+        wait: Some(true),
+        points: Some(Filter::must([Condition::matches("session_id", session_id.to_string())]).into()),
+        ordering: None,
+        shard_key_selector: None,
+    }).await;
+    record_qdrant("delete", started.elapsed(), result.is_ok());
+    result.map_err(|e| AppError::QdrantError(e.to_string()))?;
 
     Ok(())
 }
 
+// [ขอบเขตของ branch ที่ใช้อยู่: id ของข้อความใน branch + id ของทุกข้อความใน log (ทุก branch)]
+#[derive(Debug, Clone, Copy)]
+pub struct BranchScope<'a> {
+    pub branch_ids: &'a [String],
+    pub logged_ids: &'a [String],
+}
+
+// -----------------------
+// point ที่นับว่าอยู่ใน branch:
+// 1. ข้อความของ branch นี้ (point id = message id)
+// 2. summary ที่สรุปถึงข้อความใน branch นี้ (payload parent_id)
+// 3. point เก่าที่ id ไม่ตรงกับข้อความใดใน log และไม่มี parent_id
+//    (ก่อนมี branch point id เป็น uuid สุ่ม, log ยุคนั้นเป็นเส้นเดียวจึงนับรวมทั้ง session เหมือนเดิม)
+// -----------------------
+fn branch_filter(scope: BranchScope) -> Filter {
+    let ids = |ids: &[String]| Condition::has_id(ids.iter().map(|id| PointId::from(id.clone())));
+
+    Filter::should([
+        ids(scope.branch_ids),
+        Filter::must([
+            Condition::matches("role", "summary".to_string()),
+            Condition::matches("parent_id", scope.branch_ids.to_vec()),
+        ]).into(),
+        Filter {
+            must: vec![Condition::is_empty("parent_id")],
+            must_not: vec![ids(scope.logged_ids)],
+            ..Default::default()
+        }.into(),
+    ])
+}
+
+// [point ของ session นี้ ถ้ามี scope จำกัดเฉพาะ branch นั้น]
+pub fn memory_filter(session_id: &str, scope: Option<BranchScope<'_>>) -> Filter {
+    let mut must = vec![Condition::matches("session_id", session_id.to_string())];
+    if let Some(scope) = scope {
+        must.push(branch_filter(scope).into());
+    }
+    Filter { must, ..Default::default() }
+}
+
 pub async fn search_context_from_qdrant(
     client: &Qdrant,
+    config: &QdrantConfig,
     session_id: &str,
+    scope: BranchScope<'_>,
+    query: &str,
     query_embedding: Vec<f32>,
+    limit: u64,
 ) -> AppResult<Vec<ChatMessage>> {
-    let res = client.search_points(SearchPoints {
-        collection_name: "chat_memory".to_string(),
-        vector: query_embedding,
-        limit: 10,
-        filter: Some(Filter {
-            must: vec![Condition::matches("session_id", session_id.to_string())],
+    // [ค้นเฉพาะ point ของ branch ที่ใช้อยู่ ข้อความของ branch อื่นจะไม่โผล่มาใน prompt]
+    if scope.branch_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let hits = search_memory(client, config, session_id, Some(scope), query, query_embedding, limit).await?;
+
+    Ok(hits.into_iter().map(|hit| hit.message).collect())
+}
+
+#[derive(Debug)]
+pub struct MemoryHit {
+    pub message: ChatMessage,
+    pub score: f32,
+}
+
+// [scope = None ค้นทุก point ของ session รวม summary และ branch อื่น]
+// [hybrid: ค้นทั้ง dense และ sparse แล้วรวมอันดับด้วย RRF, score ที่ได้จึงเป็นคะแนน RRF ไม่ใช่ cosine]
+pub async fn search_memory(
+    client: &Qdrant,
+    config: &QdrantConfig,
+    session_id: &str,
+    scope: Option<BranchScope<'_>>,
+    query: &str,
+    query_embedding: Vec<f32>,
+    limit: u64,
+) -> AppResult<Vec<MemoryHit>> {
+    let filter = memory_filter(session_id, scope);
+
+    // [query ที่ไม่มี token เลย (เช่นมีแต่เครื่องหมาย) ใช้ dense อย่างเดียว]
+    let sparse = if config.hybrid { query_vector(query) } else { Vec::new() };
+
+    let started = Instant::now();
+    let points = if sparse.is_empty() {
+        let res = client.search_points(SearchPoints {
+            collection_name: config.collection.clone(),
+            vector: query_embedding,
+            limit,
+            filter: Some(filter),
+            with_payload: Some(true.into()),
             ..Default::default()
-        }),
-        with_payload: Some(true.into()),
-        ..Default::default()
-    }).await.map_err(|e| AppError::QdrantError(e.to_string()))?;
+        }).await;
+        record_qdrant("search", started.elapsed(), res.is_ok());
+        res.map_err(|e| AppError::QdrantError(e.to_string()))?.result
+    } else {
+        let prefetch_limit = limit * PREFETCH_MULTIPLIER;
+        let res = client.query(
+            QueryPointsBuilder::new(&config.collection)
+                .add_prefetch(PrefetchQueryBuilder::default()
+                    .query(Query::new_nearest(query_embedding))
+                    .filter(filter.clone())
+                    .limit(prefetch_limit))
+                .add_prefetch(PrefetchQueryBuilder::default()
+                    .query(Query::new_nearest(sparse.as_slice()))
+                    .using(SPARSE_VECTOR)
+                    .filter(filter)
+                    .limit(prefetch_limit))
+                .query(Query::new_fusion(Fusion::Rrf))
+                .limit(limit)
+                .with_payload(true),
+        ).await;
+        record_qdrant("hybrid_search", started.elapsed(), res.is_ok());
+        res.map_err(|e| AppError::QdrantError(e.to_string()))?.result
+    };
 
-    let history = res.result.into_iter()
-        .filter_map(|point| {
-            let ts = point.payload.get("timestamp")?;
-            let timestamp: i64 = ts.clone().try_into().ok()?;
-            Some(ChatMessage {
-                session_id: point.payload.get("session_id")?.as_str()?.to_string(),
-                role: point.payload.get("role")?.as_str()?.to_string(),
-                content: point.payload.get("content")?.as_str()?.to_string(),
-                timestamp: chrono::DateTime::from_timestamp(timestamp, 0)?,
-            })
-        })
-        .collect();
+    Ok(points.into_iter().filter_map(memory_hit).collect())
+}
 
+fn memory_hit(point: ScoredPoint) -> Option<MemoryHit> {
+    let ts = point.payload.get("timestamp")?;
+    let timestamp: i64 = ts.clone().try_into().ok()?;
+    let message = ChatMessage {
+        timestamp: chrono::DateTime::from_timestamp(timestamp, 0)?,
+        ..ChatMessage::new(
+            &point_id_string(point.id.as_ref()?)?,
+            point.payload.get("session_id")?.as_str()?,
+            point.payload.get("role")?.as_str()?,
+            point.payload.get("content")?.as_str()?,
+        )
+    };
+
+    Some(MemoryHit { message, score: point.score })
+}
 
-    Ok(history)
+fn point_id_string(id: &PointId) -> Option<String> {
+    match id.point_id_options.as_ref()? {
+        PointIdOptions::Uuid(uuid) => Some(uuid.clone()),
+        PointIdOptions::Num(num) => Some(num.to_string()),
+    }
 }
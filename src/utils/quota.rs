@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::RwLock;
+
+use chrono::Utc;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaLimits {
+    pub daily_tokens: Option<u64>,
+    pub monthly_tokens: Option<u64>,
+    pub daily_usd: Option<f64>,
+    pub monthly_usd: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExceededMode {
+    // [ตอบกลับเป็นบทพูดของราพี แทนการเรียก OpenAI]
+    #[default]
+    Persona,
+    // [ตอบ 429 (user) / 402 (global)]
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaSettings {
+    pub user: QuotaLimits,
+    pub global: QuotaLimits,
+    pub users: HashMap<String, QuotaLimits>,
+    pub mode: ExceededMode,
+    pub kill_switch: bool,
+    pub price_input_per_1k_usd: f64,
+    pub price_output_per_1k_usd: f64,
+    pub price_embedding_per_1k_usd: f64,
+}
+
+impl Default for QuotaSettings {
+    fn default() -> Self {
+        Self {
+            user: QuotaLimits::default(),
+            global: QuotaLimits::default(),
+            users: HashMap::new(),
+            mode: ExceededMode::default(),
+            kill_switch: false,
+            // [ราคา gpt-4o]
+            price_input_per_1k_usd: 0.0025,
+            price_output_per_1k_usd: 0.01,
+            // [ราคา text-embedding-3-small]
+            price_embedding_per_1k_usd: 0.00002,
+        }
+    }
+}
+
+// -----------------------
+// ค่าที่แอดมินปรับผ่าน API ทับค่าจาก config
+// บันทึกลง quota_file คู่กับ usage ส่วนราคาและค่าอื่นอ่านจาก config ทุกครั้งที่ start
+// -----------------------
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<QuotaLimits>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub global: Option<QuotaLimits>,
+    // [None = ลบค่ารายคนที่มาจาก config]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub users: HashMap<String, Option<QuotaLimits>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<ExceededMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kill_switch: Option<bool>,
+}
+
+impl QuotaOverrides {
+    pub fn apply(&self, config: &QuotaSettings) -> QuotaSettings {
+        let mut settings = config.clone();
+
+        if let Some(user) = self.user {
+            settings.user = user;
+        }
+        if let Some(global) = self.global {
+            settings.global = global;
+        }
+        for (user_id, limits) in &self.users {
+            match limits {
+                Some(limits) => settings.users.insert(user_id.clone(), *limits),
+                None => settings.users.remove(user_id),
+            };
+        }
+        if let Some(mode) = self.mode {
+            settings.mode = mode;
+        }
+        if let Some(kill_switch) = self.kill_switch {
+            settings.kill_switch = kill_switch;
+        }
+
+        settings
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Usage {
+    pub day: String,
+    pub day_tokens: u64,
+    pub day_usd: f64,
+    pub month: String,
+    pub month_tokens: u64,
+    pub month_usd: f64,
+}
+
+impl Usage {
+    // [ข้ามวัน/ข้ามเดือนแล้ว reset ตัวนับ]
+    fn roll(&mut self) {
+        let now = Utc::now();
+        let day = now.format("%Y-%m-%d").to_string();
+        let month = now.format("%Y-%m").to_string();
+
+        if self.day != day {
+            self.day = day;
+            self.day_tokens = 0;
+            self.day_usd = 0.0;
+        }
+        if self.month != month {
+            self.month = month;
+            self.month_tokens = 0;
+            self.month_usd = 0.0;
+        }
+    }
+
+    fn add(&mut self, tokens: u64, usd: f64) {
+        self.roll();
+        self.day_tokens += tokens;
+        self.day_usd += usd;
+        self.month_tokens += tokens;
+        self.month_usd += usd;
+    }
+
+    fn exceeds(&self, limits: &QuotaLimits) -> Option<&'static str> {
+        let over = |used: f64, limit: Option<f64>| limit.is_some_and(|l| used >= l);
+
+        if over(self.day_tokens as f64, limits.daily_tokens.map(|v| v as f64)) {
+            Some("daily token quota")
+        } else if over(self.month_tokens as f64, limits.monthly_tokens.map(|v| v as f64)) {
+            Some("monthly token quota")
+        } else if over(self.day_usd, limits.daily_usd) {
+            Some("daily budget")
+        } else if over(self.month_usd, limits.monthly_usd) {
+            Some("monthly budget")
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuotaBreach {
+    User(String),
+    Global(String),
+}
+
+impl QuotaBreach {
+    pub fn into_error(self) -> AppError {
+        match self {
+            QuotaBreach::User(reason) => AppError::QuotaExceeded(format!("User {reason} exceeded")),
+            QuotaBreach::Global(reason) => AppError::PaymentRequired(format!("Global {reason} exceeded")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Snapshot {
+    overrides: QuotaOverrides,
+    global: Usage,
+    users: HashMap<String, Usage>,
+}
+
+pub struct QuotaManager {
+    config: QuotaSettings,
+    overrides: RwLock<QuotaOverrides>,
+    // [= overrides.apply(config) คำนวณไว้ตอนแก้ ไม่ต้องรวมใหม่ทุก request]
+    settings: RwLock<QuotaSettings>,
+    global: RwLock<Usage>,
+    users: DashMap<String, Usage>,
+    path: String,
+    // [เขียนไฟล์ทีละครั้ง ไม่งั้น snapshot เก่าอาจเขียนทับอันใหม่]
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+impl QuotaManager {
+    // [โหลด usage + ค่าที่แอดมินเคยปรับไว้จากไฟล์ ถ้ามี ไฟล์เสียให้ start ไม่ผ่าน (ไม่ reset usage เงียบ ๆ)]
+    pub fn load(config: QuotaSettings, path: &str) -> AppResult<Self> {
+        let snapshot: Snapshot = match std::fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .map_err(|e| AppError::ConfigError(format!("{path}: {e}")))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Snapshot::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            settings: RwLock::new(snapshot.overrides.apply(&config)),
+            overrides: RwLock::new(snapshot.overrides),
+            config,
+            global: RwLock::new(snapshot.global),
+            users: snapshot.users.into_iter().collect(),
+            path: path.to_string(),
+            write_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn settings(&self) -> QuotaSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    pub fn update_overrides(&self, update: impl FnOnce(&mut QuotaOverrides)) {
+        let mut overrides = self.overrides.write().unwrap();
+        update(&mut overrides);
+        *self.settings.write().unwrap() = overrides.apply(&self.config);
+    }
+
+    // [ตรวจแค่ usage ที่บันทึกแล้ว ไม่ได้จอง token ของ request ที่กำลังรอคำตอบ (soft limit ดู config.example.toml)]
+    pub fn check(&self, user_id: &str) -> Result<(), QuotaBreach> {
+        let settings = self.settings.read().unwrap();
+
+        if settings.kill_switch {
+            return Err(QuotaBreach::Global("kill switch".into()));
+        }
+
+        {
+            let mut global = self.global.write().unwrap();
+            global.roll();
+            if let Some(reason) = global.exceeds(&settings.global) {
+                return Err(QuotaBreach::Global(reason.into()));
+            }
+        }
+
+        let limits = settings.users.get(user_id).unwrap_or(&settings.user);
+        let mut usage = self.users.entry(user_id.to_string()).or_default();
+        usage.roll();
+        if let Some(reason) = usage.exceeds(limits) {
+            return Err(QuotaBreach::User(reason.into()));
+        }
+
+        Ok(())
+    }
+
+    pub fn record(&self, user_id: &str, prompt_tokens: u64, completion_tokens: u64) {
+        let settings = self.settings.read().unwrap();
+        let usd = prompt_tokens as f64 / 1000.0 * settings.price_input_per_1k_usd
+            + completion_tokens as f64 / 1000.0 * settings.price_output_per_1k_usd;
+        let tokens = prompt_tokens + completion_tokens;
+
+        drop(settings);
+        self.add(user_id, tokens, usd);
+    }
+
+    pub fn record_embedding(&self, user_id: &str, tokens: u64) {
+        let usd = tokens as f64 / 1000.0 * self.settings.read().unwrap().price_embedding_per_1k_usd;
+        self.add(user_id, tokens, usd);
+    }
+
+    fn add(&self, user_id: &str, tokens: u64, usd: f64) {
+        self.global.write().unwrap().add(tokens, usd);
+        self.users.entry(user_id.to_string()).or_default().add(tokens, usd);
+    }
+
+    pub fn usage(&self, user_id: &str) -> Usage {
+        self.users.get(user_id).map(|u| u.clone()).unwrap_or_default()
+    }
+
+    pub fn global_usage(&self) -> Usage {
+        self.global.read().unwrap().clone()
+    }
+
+    // [เขียนผ่านไฟล์ .tmp แล้ว rename ไฟล์จริงจึงไม่มีวันเป็นครึ่ง ๆ]
+    pub async fn persist(&self) -> AppResult<()> {
+        let _guard = self.write_lock.lock().await;
+
+        let snapshot = Snapshot {
+            overrides: self.overrides.read().unwrap().clone(),
+            global: self.global_usage(),
+            users: self.users.iter().map(|e| (e.key().clone(), e.value().clone())).collect(),
+        };
+
+        if let Some(dir) = Path::new(&self.path).parent().filter(|d| !d.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        let json = serde_json::to_string_pretty(&snapshot)?;
+        let tmp_path = format!("{}.tmp", self.path);
+        tokio::fs::write(&tmp_path, json).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+
+        Ok(())
+    }
+}
+
+// [รูป 1 รูปคิดเท่านี้ (ราว ๆ ภาพ 1024px ที่ detail: high)]
+pub const IMAGE_TOKENS_ESTIMATE: u64 = 765;
+
+// [ประมาณ token จากความยาวข้อความ ใช้ตอน provider ไม่ได้ส่ง usage มา
+//  ภาษาไทยกิน token มากกว่าอังกฤษ นับ 1 token ต่อ 2 ตัวอักษรให้ประมาณเกินไว้ก่อน]
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(2)
+}
+
+pub fn off_duty_reply() -> String {
+    "ผู้บัญชาการคะ ตอนนี้ราพีออกเวรแล้วค่ะ งบปฏิบัติการหมดแล้ว ไว้ค่อยคุยกันใหม่นะคะ".to_string()
+}
@@ -0,0 +1,124 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::app::config::ResilienceConfig;
+use crate::app::error::AppError;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &ResilienceConfig) -> Self {
+        Self {
+            max_retries: config.max_retries,
+            base_delay: Duration::from_millis(config.base_delay_ms),
+            max_delay: Duration::from_millis(config.max_delay_ms),
+        }
+    }
+
+    // [full jitter: สุ่ม 0..base*2^attempt, ถ้า provider ส่ง Retry-After มาให้เชื่อตามนั้น]
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(after) = retry_after {
+            return after.min(self.max_delay);
+        }
+
+        let exp = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        let cap = exp.min(self.max_delay).as_millis() as u64;
+
+        Duration::from_millis(fastrand::u64(0..=cap))
+    }
+}
+
+// [error แบบไหนควรลองใหม่: rate limit ชั่วคราว, 5xx, timeout/ต่อไม่ติด]
+pub fn should_retry(error: &AppError) -> bool {
+    matches!(
+        error,
+        AppError::UpstreamRateLimited(_) | AppError::UpstreamUnavailable(_) | AppError::ReqwestError(_)
+    )
+}
+
+// [error ที่แปลว่า provider ล่ม (ไม่นับ 4xx ที่เป็นความผิดของ request)]
+fn is_outage(error: &AppError) -> bool {
+    matches!(error, AppError::UpstreamUnavailable(_) | AppError::ReqwestError(_))
+}
+
+#[derive(Debug)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    // [ปล่อยให้ลองได้ 1 request หลังครบเวลา open, ถ้า probe หายไปเฉย ๆ ให้ลองใหม่เมื่อครบเวลาอีกรอบ]
+    HalfOpen { since: Instant },
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    state: Mutex<BreakerState>,
+    failure_threshold: u32,
+    open_for: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn from_config(config: &ResilienceConfig) -> Self {
+        Self {
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+            failure_threshold: config.breaker_failure_threshold,
+            open_for: Duration::from_secs(config.breaker_open_secs),
+        }
+    }
+
+    pub fn acquire(&self, name: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+
+        match *state {
+            BreakerState::Closed { .. } => Ok(()),
+            BreakerState::Open { until } if Instant::now() >= until => {
+                *state = BreakerState::HalfOpen { since: Instant::now() };
+                Ok(())
+            }
+            BreakerState::HalfOpen { since } if since.elapsed() >= self.open_for => {
+                *state = BreakerState::HalfOpen { since: Instant::now() };
+                Ok(())
+            }
+            BreakerState::Open { until } => Err(AppError::UpstreamUnavailable(format!(
+                "{name} circuit open, retry in {}s",
+                until.saturating_duration_since(Instant::now()).as_secs().max(1)
+            ))),
+            BreakerState::HalfOpen { .. } => Err(AppError::UpstreamUnavailable(format!(
+                "{name} circuit half-open, probing"
+            ))),
+        }
+    }
+
+    pub fn record<T>(&self, name: &str, result: &Result<T, AppError>) {
+        let mut state = self.state.lock().unwrap();
+
+        match result {
+            Err(e) if is_outage(e) => {
+                let failures = match *state {
+                    BreakerState::Closed { failures } => failures + 1,
+                    _ => self.failure_threshold,
+                };
+
+                if failures >= self.failure_threshold {
+                    tracing::warn!(upstream = name, "Circuit breaker opened");
+                    *state = BreakerState::Open { until: Instant::now() + self.open_for };
+                } else {
+                    *state = BreakerState::Closed { failures };
+                }
+            }
+            _ => {
+                if !matches!(*state, BreakerState::Closed { failures: 0 }) {
+                    *state = BreakerState::Closed { failures: 0 };
+                }
+            }
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), BreakerState::Open { until } if Instant::now() < until)
+    }
+}
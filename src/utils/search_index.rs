@@ -0,0 +1,362 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection};
+use serde::Serialize;
+
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+use crate::app::state::AppState;
+use crate::controllers::chat::{list_session_ids, load_full_messages, ChatMessage};
+use crate::utils::export::escape_html;
+use crate::utils::image::ensure_dir_once;
+use crate::utils::session_lock::lock_session;
+use crate::utils::session_meta::load_meta;
+
+// -----------------------
+// messages = ตารางปกติ (มี index) เก็บ id/เจ้าของ/เวลา, messages_fts เก็บแค่ content โดยใช้ rowid เดียวกัน
+// ลบ/แทนที่ข้อความด้วย rowid ไม่ต้อง scan ทั้ง FTS table
+// trigram ของ FTS5 ค้นแบบ substring ได้โดยไม่ต้องตัดคำ ภาษาไทยที่ไม่มีเว้นวรรคจึงค้นเจอ
+// -----------------------
+const SCHEMA_VERSION: i64 = 1;
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS messages (
+        rowid INTEGER PRIMARY KEY,
+        message_id TEXT NOT NULL UNIQUE,
+        session_id TEXT NOT NULL,
+        owner TEXT NOT NULL,
+        role TEXT NOT NULL,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS messages_session ON messages (session_id);
+    CREATE INDEX IF NOT EXISTS messages_owner ON messages (owner, timestamp);
+    CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(content, tokenize = 'trigram');
+";
+
+// [trigram ค้นคำที่สั้นกว่า 3 ตัวอักษรไม่ได้ ต้องใช้ LIKE แทน]
+const MIN_MATCH_CHARS: usize = 3;
+const SNIPPET_CONTEXT_CHARS: usize = 40;
+const SNIPPET_MAX_CHARS: usize = 160;
+
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    pub text: String,
+    // [ค้นเฉพาะ session ของ user นี้ ว่าง = ไม่รับ query (ยกเว้น all_owners)]
+    pub owner: String,
+    // [admin เท่านั้น: ค้นทุก user ไม่สน owner]
+    pub all_owners: bool,
+    pub session_id: Option<String>,
+    pub role: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchHit {
+    pub session_id: String,
+    pub message_id: String,
+    pub role: String,
+    pub timestamp: DateTime<Utc>,
+    // [ข้อความรอบคำที่เจอ escape HTML แล้ว ครอบคำที่ตรงด้วย <mark>]
+    pub highlight: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchResults {
+    pub total: usize,
+    pub hits: Vec<SearchHit>,
+}
+
+fn index_error(e: impl std::fmt::Display) -> AppError {
+    AppError::InternalError(format!("Search index error: {e}"))
+}
+
+// -----------------------
+// Full-text index (SQLite FTS5) แยกจาก chat log, สร้างใหม่จาก log ได้ทุกเมื่อ
+// rusqlite เป็น sync ทุก call จึงวิ่งใน spawn_blocking
+// -----------------------
+pub struct SearchIndex {
+    conn: Mutex<Connection>,
+}
+
+impl SearchIndex {
+    pub fn open(path: &str) -> AppResult<Self> {
+        if let Some(dir) = Path::new(path).parent().and_then(|d| d.to_str()).filter(|d| !d.is_empty()) {
+            ensure_dir_once(dir)?;
+        }
+
+        let conn = Connection::open(path).map_err(index_error)?;
+
+        // [user_version ไว้ให้ schema เวอร์ชันถัดไปรู้ว่าต้อง migrate จากอะไร]
+        conn.execute_batch(SCHEMA).map_err(index_error)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION).map_err(index_error)?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    async fn with_conn<T, F>(self: &Arc<Self>, f: F) -> AppResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let this = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut conn = this.conn.lock().unwrap();
+            f(&mut conn)
+        })
+        .await
+        .map_err(index_error)?
+        .map_err(index_error)
+    }
+
+    pub async fn is_empty(self: &Arc<Self>) -> AppResult<bool> {
+        self.with_conn(|conn| {
+            conn.query_row("SELECT NOT EXISTS (SELECT 1 FROM messages)", [], |row| row.get(0))
+        }).await
+    }
+
+    // [owner = เจ้าของ session, message_id เดิมจะถูกแทนที่ (regenerate แก้ content ของ id เดิม)]
+    pub async fn index(self: &Arc<Self>, owner: &str, messages: Vec<ChatMessage>) -> AppResult<()> {
+        let owner = owner.to_string();
+
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            upsert_messages(&tx, &owner, &messages)?;
+            tx.commit()
+        }).await
+    }
+
+    pub async fn delete_session(self: &Arc<Self>, session_id: &str) -> AppResult<()> {
+        let session_id = session_id.to_string();
+
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM messages_fts WHERE rowid IN (SELECT rowid FROM messages WHERE session_id = ?1)",
+                params![session_id],
+            )?;
+            tx.execute("DELETE FROM messages WHERE session_id = ?1", params![session_id])?;
+            tx.commit()
+        }).await
+    }
+
+    pub async fn session_ids(self: &Arc<Self>) -> AppResult<Vec<String>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT DISTINCT session_id FROM messages")?;
+            let ids = stmt.query_map([], |row| row.get(0))?.collect();
+            ids
+        }).await
+    }
+
+    pub async fn search(self: &Arc<Self>, query: SearchQuery) -> AppResult<SearchResults> {
+        if query.owner.is_empty() && !query.all_owners {
+            return Err(AppError::InternalError("Search query is not scoped to a user".into()));
+        }
+
+        self.with_conn(move |conn| run_search(conn, &query)).await
+    }
+}
+
+fn upsert_messages(conn: &Connection, owner: &str, messages: &[ChatMessage]) -> rusqlite::Result<()> {
+    let mut upsert = conn.prepare_cached(
+        "INSERT INTO messages (message_id, session_id, owner, role, timestamp) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (message_id) DO UPDATE SET
+             session_id = excluded.session_id, owner = excluded.owner,
+             role = excluded.role, timestamp = excluded.timestamp
+         RETURNING rowid",
+    )?;
+    let mut delete = conn.prepare_cached("DELETE FROM messages_fts WHERE rowid = ?1")?;
+    let mut insert = conn.prepare_cached("INSERT INTO messages_fts (rowid, content) VALUES (?1, ?2)")?;
+
+    for message in messages.iter().filter(|m| is_searchable(m)) {
+        let rowid: i64 = upsert.query_row(
+            params![message.id, message.session_id, owner, message.role, message.timestamp.timestamp()],
+            |row| row.get(0),
+        )?;
+        delete.execute(params![rowid])?;
+        insert.execute(params![rowid, message.content])?;
+    }
+
+    Ok(())
+}
+
+fn is_searchable(message: &ChatMessage) -> bool {
+    (message.role == "user" || message.role == "assistant") && !message.content.trim().is_empty()
+}
+
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+// [ทุกคำต้องเจอ (AND) คำยาวใช้ MATCH เรียงตาม bm25, ถ้ามีแต่คำสั้นเรียงตามเวลาใหม่สุดก่อน]
+fn run_search(conn: &Connection, query: &SearchQuery) -> rusqlite::Result<SearchResults> {
+    let terms: Vec<&str> = query.text.split_whitespace().collect();
+    let (long, short): (Vec<&str>, Vec<&str>) = terms.iter().partition(|t| t.chars().count() >= MIN_MATCH_CHARS);
+
+    let mut conditions: Vec<String> = Vec::new();
+    let mut values: Vec<Value> = Vec::new();
+
+    if !query.all_owners {
+        conditions.push("m.owner = ?".to_string());
+        values.push(Value::Text(query.owner.clone()));
+    }
+
+    if !long.is_empty() {
+        let phrase = long.iter().map(|t| format!("\"{}\"", t.replace('"', "\"\""))).collect::<Vec<_>>().join(" ");
+        conditions.push("messages_fts MATCH ?".to_string());
+        values.push(Value::Text(phrase));
+    }
+    for term in &short {
+        conditions.push("messages_fts.content LIKE ? ESCAPE '\\'".to_string());
+        values.push(Value::Text(format!("%{}%", escape_like(term))));
+    }
+    if let Some(session_id) = &query.session_id {
+        conditions.push("m.session_id = ?".to_string());
+        values.push(Value::Text(session_id.clone()));
+    }
+    if let Some(role) = &query.role {
+        conditions.push("m.role = ?".to_string());
+        values.push(Value::Text(role.clone()));
+    }
+    if let Some(since) = query.since {
+        conditions.push("m.timestamp >= ?".to_string());
+        values.push(Value::Integer(since.timestamp()));
+    }
+    if let Some(until) = query.until {
+        conditions.push("m.timestamp <= ?".to_string());
+        values.push(Value::Integer(until.timestamp()));
+    }
+
+    let from = "messages_fts JOIN messages m ON m.rowid = messages_fts.rowid";
+    // [admin ค้นทุก user แต่ไม่มีคำค้นเลย (เรียกตรงไม่ผ่าน /api/search) = ไม่มีเงื่อนไข]
+    let where_clause = if conditions.is_empty() { "1".to_string() } else { conditions.join(" AND ") };
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {from} WHERE {where_clause}"),
+        params_from_iter(values.iter()),
+        |row| row.get(0),
+    )?;
+
+    let order = if long.is_empty() { "m.timestamp DESC" } else { "messages_fts.rank" };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT messages_fts.content, m.message_id, m.session_id, m.role, m.timestamp FROM {from}
+         WHERE {where_clause} ORDER BY {order} LIMIT {} OFFSET {}",
+        query.limit, query.offset,
+    ))?;
+
+    let hits = stmt
+        .query_map(params_from_iter(values.iter()), |row| {
+            let content: String = row.get(0)?;
+            let timestamp: i64 = row.get(4)?;
+
+            Ok(SearchHit {
+                message_id: row.get(1)?,
+                session_id: row.get(2)?,
+                role: row.get(3)?,
+                timestamp: Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default(),
+                highlight: highlight(&content, &terms),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(SearchResults { total: total as usize, hits })
+}
+
+// -----------------------
+// ตัดข้อความรอบคำแรกที่เจอ แล้วครอบทุกคำที่ตรงด้วย <mark> (ไม่สนตัวพิมพ์เล็กใหญ่)
+// -----------------------
+pub fn highlight(content: &str, terms: &[&str]) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let folded: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for term in terms {
+        let needle: Vec<char> = term.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect();
+        if needle.is_empty() || needle.len() > folded.len() {
+            continue;
+        }
+        let mut i = 0;
+        while i + needle.len() <= folded.len() {
+            if folded[i..i + needle.len()] == needle[..] {
+                ranges.push((i, i + needle.len()));
+                i += needle.len();
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    ranges.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let window_start = merged.first().map(|r| r.0.saturating_sub(SNIPPET_CONTEXT_CHARS)).unwrap_or(0);
+    let window_end = (window_start + SNIPPET_MAX_CHARS).min(chars.len());
+
+    let mut out = String::new();
+    if window_start > 0 {
+        out.push('…');
+    }
+
+    let mut cursor = window_start;
+    for (start, end) in merged {
+        if start >= window_end {
+            break;
+        }
+        let end = end.min(window_end);
+        out.push_str(&escape_html(&chars[cursor..start].iter().collect::<String>()));
+        out.push_str("<mark>");
+        out.push_str(&escape_html(&chars[start..end].iter().collect::<String>()));
+        out.push_str("</mark>");
+        cursor = end;
+    }
+    out.push_str(&escape_html(&chars[cursor..window_end].iter().collect::<String>()));
+
+    if window_end < chars.len() {
+        out.push('…');
+    }
+    out
+}
+
+// -----------------------
+// sync index กับ chat log ทั้งหมด คืนจำนวน session ที่อ่านได้
+// ไม่ล้างทั้งตาราง: upsert ทีละ session ภายใต้ lock ของ session นั้น (แชทที่กำลังบันทึกไม่หาย)
+// แล้วค่อยลบ session ที่ไม่มี log แล้ว
+// -----------------------
+pub async fn reindex_all(state: &AppState) -> AppResult<usize> {
+    let storage = &state.config.storage;
+    let mut sessions = 0;
+
+    for session_id in list_session_ids(&storage.chat_logs_dir).await? {
+        let _lock = lock_session(&session_id).await;
+        let log = match load_full_messages(&storage.chat_logs_dir, &session_id).await {
+            Ok(log) => log,
+            Err(e) => {
+                tracing::warn!(session_id, error = %e, "Skipping unreadable chat log");
+                continue;
+            }
+        };
+        let meta = load_meta(&storage.sessions_dir, &session_id).await?;
+
+        state.search.index(meta.owner(), log).await?;
+        sessions += 1;
+    }
+
+    for session_id in state.search.session_ids().await? {
+        let _lock = lock_session(&session_id).await;
+        if !Path::new(&format!("{}/{}.json", storage.chat_logs_dir, session_id)).exists() {
+            state.search.delete_session(&session_id).await?;
+        }
+    }
+
+    Ok(sessions)
+}
@@ -0,0 +1,17 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+// -----------------------
+// Lock ต่อ session
+// chat log และ metadata แก้แบบอ่านทั้งไฟล์ -> แก้ -> เขียนทับ
+// ทุกที่ที่แก้ไฟล์ของ session ต้องถือ lock นี้ ไม่งั้น turn ที่บันทึกพร้อม regenerate/feedback จะเขียนทับกัน
+// -----------------------
+static SESSION_LOCKS: Lazy<DashMap<String, Arc<Mutex<()>>>> = Lazy::new(DashMap::new);
+
+pub async fn lock_session(session_id: &str) -> OwnedMutexGuard<()> {
+    let lock = SESSION_LOCKS.entry(session_id.to_string()).or_default().clone();
+    lock.lock_owned().await
+}
@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::app::auth::ANONYMOUS_USER;
+use crate::app::result::AppResult;
+use crate::utils::image::ensure_dir_once;
+
+// -----------------------
+// Session metadata
+// เก็บแยกจาก chat log ที่ storage.sessions_dir/{session_id}.json
+// -----------------------
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SessionMeta {
+    // [ข้อความสุดท้ายของ branch ที่ใช้อยู่ ไม่มี = ข้อความล่าสุดในไฟล์]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_leaf: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    // [persona default ของ session ถ้า request ไม่ได้ระบุ]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    // [user ที่สร้าง session ไม่มี = session เก่าก่อนมี auth (ของ anonymous)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+impl SessionMeta {
+    pub fn owner(&self) -> &str {
+        self.owner.as_deref().unwrap_or(ANONYMOUS_USER)
+    }
+}
+
+fn meta_path(dir_path: &str, session_id: &str) -> String {
+    format!("{}/{}.json", dir_path, session_id)
+}
+
+pub async fn load_meta(dir_path: &str, session_id: &str) -> AppResult<SessionMeta> {
+    let file_path = meta_path(dir_path, session_id);
+
+    if !Path::new(&file_path).exists() {
+        return Ok(SessionMeta::default());
+    }
+
+    let content = fs::read_to_string(&file_path).await?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+pub async fn save_meta(dir_path: &str, session_id: &str, meta: &SessionMeta) -> AppResult<()> {
+    ensure_dir_once(dir_path)?;
+
+    let json = serde_json::to_string_pretty(meta)?;
+    fs::write(meta_path(dir_path, session_id), json).await?;
+
+    Ok(())
+}
+
+pub async fn set_active_leaf(dir_path: &str, session_id: &str, leaf_id: &str) -> AppResult<()> {
+    let mut meta = load_meta(dir_path, session_id).await?;
+    meta.active_leaf = Some(leaf_id.to_string());
+    save_meta(dir_path, session_id, &meta).await
+}
+
+// [อ่านใหม่ก่อนเขียน ไม่ทับ active_leaf ที่ turn อื่นเพิ่งตั้ง]
+pub async fn set_title(dir_path: &str, session_id: &str, title: &str) -> AppResult<()> {
+    let mut meta = load_meta(dir_path, session_id).await?;
+    meta.title = Some(title.to_string());
+    save_meta(dir_path, session_id, &meta).await
+}
+
+pub async fn delete_meta(dir_path: &str, session_id: &str) -> AppResult<()> {
+    match fs::remove_file(meta_path(dir_path, session_id)).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
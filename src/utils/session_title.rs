@@ -0,0 +1,108 @@
+use tracing::warn;
+
+use crate::app::error::AppError;
+use crate::app::result::AppResult;
+use crate::app::state::AppState;
+use crate::controllers::chat::{load_active_branch, ChatMessage, MessageRequest};
+use crate::utils::completion::complete_with_fallback;
+use crate::utils::model_router::Task;
+use crate::utils::quota::QuotaBreach;
+use crate::utils::session_lock::lock_session;
+use crate::utils::session_meta::{load_meta, set_title};
+
+// [ส่งแค่ช่วงต้นของบทสนทนา พอให้รู้หัวข้อ]
+const TITLE_CONTEXT_MESSAGES: usize = 6;
+const TITLE_CONTEXT_CHARS: usize = 500;
+const TITLE_MAX_CHARS: usize = 60;
+
+// -----------------------
+// ตั้งชื่อ session จากข้อความใน branch ด้วยโมเดลของงาน title_generation (นับเข้าโควต้าของ user_id)
+// -----------------------
+pub async fn generate_title(state: &AppState, user_id: &str, messages: &[ChatMessage]) -> AppResult<String> {
+    let mut history_text = String::new();
+
+    for msg in messages.iter().filter(|m| m.role == "user" || m.role == "assistant").take(TITLE_CONTEXT_MESSAGES) {
+        let content: String = msg.content.chars().take(TITLE_CONTEXT_CHARS).collect();
+        history_text.push_str(&format!("[{}]: {}\n", msg.role, content));
+    }
+
+    if history_text.is_empty() {
+        return Err(AppError::BadRequest("Session has no messages to title".into()));
+    }
+
+    let system_prompt = "ตั้งชื่อบทสนทนานี้สั้น ๆ ไม่เกิน 6 คำ ใช้ภาษาเดียวกับที่ผู้ใช้พิมพ์ \
+        ตอบเฉพาะชื่อ ไม่ต้องมีเครื่องหมายคำพูดหรือคำอธิบาย";
+
+    let prompt = [
+        MessageRequest::text("system", system_prompt),
+        MessageRequest::text("user", &history_text),
+    ];
+
+    state.quota.check(user_id).map_err(QuotaBreach::into_error)?;
+
+    let completion = complete_with_fallback(
+        state,
+        Task::TitleGeneration,
+        state.router.route(Task::TitleGeneration),
+        &prompt,
+    ).await?;
+
+    if let Some(usage) = &completion.usage {
+        state.quota.record(user_id, usage.prompt_tokens, usage.completion_tokens);
+        if let Err(e) = state.quota.persist().await {
+            warn!(error = %e, "Failed to persist quota usage");
+        }
+    }
+
+    clean_title(&completion.reply)
+        .ok_or_else(|| AppError::UpstreamError("Model returned an empty title".into()))
+}
+
+// [ตัดบรรทัดแรก เครื่องหมายคำพูด และ prefix แบบ "Title:" ที่โมเดลชอบแถมมา]
+pub fn clean_title(raw: &str) -> Option<String> {
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = ["Title:", "title:", "ชื่อ:"]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+        .unwrap_or(line);
+
+    let title = line
+        .trim()
+        .trim_matches(|c: char| matches!(c, '"' | '\'' | '“' | '”' | '「' | '」' | '*' | '#'))
+        .trim_end_matches('.')
+        .trim();
+
+    let title: String = title.chars().take(TITLE_MAX_CHARS).collect();
+    let title = title.trim().to_string();
+
+    (!title.is_empty()).then_some(title)
+}
+
+// [เรียกหลังบันทึก turn: ตั้งชื่อครั้งเดียวเมื่อคุยครบ limits.title_after_turns และยังไม่มีชื่อ]
+pub async fn auto_title(state: &AppState, user_id: &str, session_id: &str) -> AppResult<bool> {
+    let after_turns = state.config.limits.title_after_turns;
+    if after_turns == 0 {
+        return Ok(false);
+    }
+
+    let meta = load_meta(&state.config.storage.sessions_dir, session_id).await?;
+    if meta.title.is_some() {
+        return Ok(false);
+    }
+
+    let branch = load_active_branch(state, session_id).await?;
+    if branch.iter().filter(|m| m.role == "user").count() < after_turns {
+        return Ok(false);
+    }
+
+    let title = generate_title(state, user_id, &branch).await?;
+
+    // [ระหว่างรอโมเดลผู้ใช้อาจตั้งชื่อเองแล้ว ไม่ทับ]
+    let _lock = lock_session(session_id).await;
+    if load_meta(&state.config.storage.sessions_dir, session_id).await?.title.is_some() {
+        return Ok(false);
+    }
+    set_title(&state.config.storage.sessions_dir, session_id, &title).await?;
+
+    Ok(true)
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use crate::utils::hash::stable_hash;
+
+// -----------------------
+// Sparse vector แบบ BM25 คำนวณในเครื่อง ใช้คู่กับ dense embedding
+// dense จับความหมาย แต่พลาดชื่อเฉพาะ/ตัวเลข (ชื่อหน่วย, วันที่) ส่วน sparse จับคำที่ตรงตัว
+// IDF ให้ Qdrant คำนวณเอง (Modifier::Idf) ฝั่งนี้จึงคิดแค่ส่วน TF
+// -----------------------
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+// [ความยาวเฉลี่ยของข้อความแชท (จำนวน token) ใช้ normalize ความยาวแทนค่าจริงของ collection]
+const AVG_DOC_TOKENS: f32 = 64.0;
+// [ภาษาไทยไม่มีเว้นวรรคระหว่างคำ ตัดเป็น trigram ของตัวอักษรแทน (แบบเดียวกับ search index)]
+const THAI_NGRAM: usize = 3;
+
+#[derive(PartialEq, Clone, Copy)]
+enum CharClass {
+    Thai,
+    Word,
+    Separator,
+}
+
+fn char_class(c: char) -> CharClass {
+    // [สระ/วรรณยุกต์ไทยเป็น combining mark ไม่ผ่าน is_alphanumeric จึงเช็กช่วง Unicode ไทยก่อน]
+    if ('\u{0E00}'..='\u{0E7F}').contains(&c) {
+        CharClass::Thai
+    } else if c.is_alphanumeric() {
+        CharClass::Word
+    } else {
+        CharClass::Separator
+    }
+}
+
+// [ตัวพิมพ์เล็ก, ตัดที่เครื่องหมาย/เว้นวรรค และตรงที่ภาษาเปลี่ยน คำอังกฤษ/ตัวเลขเก็บทั้งคำ ส่วนไทยเป็น trigram]
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut run: Vec<char> = Vec::new();
+    let mut run_class = CharClass::Separator;
+
+    let mut flush = |run: &mut Vec<char>, class: CharClass| {
+        match class {
+            CharClass::Thai if run.len() > THAI_NGRAM => {
+                tokens.extend(run.windows(THAI_NGRAM).map(|w| w.iter().collect::<String>()));
+            }
+            CharClass::Thai | CharClass::Word if !run.is_empty() => tokens.push(run.iter().collect()),
+            _ => {}
+        }
+        run.clear();
+    };
+
+    for c in text.chars().flat_map(char::to_lowercase) {
+        let class = char_class(c);
+        if class != run_class {
+            flush(&mut run, run_class);
+            run_class = class;
+        }
+        if class != CharClass::Separator {
+            run.push(c);
+        }
+    }
+    flush(&mut run, run_class);
+
+    tokens
+}
+
+fn token_index(token: &str) -> u32 {
+    stable_hash(token) as u32
+}
+
+// [token ที่ hash ชนกันจะถูกรวมเป็นมิติเดียว เรียงตาม index เพราะ Qdrant ไม่รับ index ซ้ำ]
+fn to_sparse(weights: BTreeMap<u32, f32>) -> Vec<(u32, f32)> {
+    weights.into_iter().collect()
+}
+
+// [น้ำหนักของข้อความที่เก็บ: BM25 TF saturation]
+pub fn document_vector(text: &str) -> Vec<(u32, f32)> {
+    let tokens = tokenize(text);
+    let length_norm = 1.0 - B + B * tokens.len() as f32 / AVG_DOC_TOKENS;
+
+    let mut counts: BTreeMap<u32, f32> = BTreeMap::new();
+    for token in &tokens {
+        *counts.entry(token_index(token)).or_default() += 1.0;
+    }
+
+    to_sparse(
+        counts
+            .into_iter()
+            .map(|(index, tf)| (index, tf * (K1 + 1.0) / (tf + K1 * length_norm)))
+            .collect(),
+    )
+}
+
+// [ฝั่ง query นับแต่ละ token ครั้งเดียว น้ำหนัก 1 ให้ IDF จาก Qdrant เป็นตัวตัดสิน]
+pub fn query_vector(text: &str) -> Vec<(u32, f32)> {
+    to_sparse(tokenize(text).iter().map(|token| (token_index(token), 1.0)).collect())
+}
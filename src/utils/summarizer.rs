@@ -1,27 +1,28 @@
 #![allow(unused)]
 
 use crate::app::result::AppResult;
+use crate::app::state::AppState;
+use crate::utils::completion::complete_with_fallback;
 use crate::utils::embedding::create_embedding;
+use crate::utils::metrics::METRICS;
+use crate::utils::model_router::Task;
 use crate::utils::qdrant::store_message_to_qdrant;
-use crate::controllers::chat::ChatMessage;
+use crate::utils::quota::QuotaBreach;
+use crate::controllers::chat::{new_message_id, ChatMessage, MessageRequest};
 
 use std::env;
 use chrono::Utc;
 use qdrant_client::Qdrant;
-use reqwest::Client;
-use serde_json::json;
 use tokio::fs;
 
+// [สรุปเฉพาะ branch ที่ส่งมา ไม่ใช่ทั้งไฟล์]
 pub async fn summarize_history(
     session_id: &str, 
-    qdrant: &Qdrant,
-    openai_key: &str,
-    model: &str
+    user_id: &str,
+    messages: &[ChatMessage],
+    state: &AppState,
 ) -> AppResult<String> {
-    let file_path = format!("data/chat_logs/{}.json", session_id);
-
-    let content = fs::read_to_string(&file_path).await?;
-    let messages: Vec<ChatMessage> = serde_json::from_str(&content)?;
+    state.quota.check(user_id).map_err(QuotaBreach::into_error)?;
 
     let mut history_text = String::new();
 
@@ -31,53 +32,38 @@ pub async fn summarize_history(
 
     let system_prompt = "สรุปบทสนทนานี้ให้เป็นย่อหน้าเดียวแบบกระชับ โดยบอกบริบทหลักที่คุยกัน เช่น 'ผู้บัญชาการชวนราพีไปเที่ยวทะเล และกำลังเลือกชุด'";
 
-    let payload = json!({
-        "model": model,
-        "messages": [
-            { "role": "system", "content": system_prompt },
-            { "role": "user", "content": history_text }
-        ]
-    });
-
-    let client = Client::new();
-    let res = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .bearer_auth(&openai_key)
-        .json(&payload)
-        .send()
-        .await?;
-
-    let body = res.text().await?;
-
-    #[derive(serde::Deserialize)]
-    struct ChoiceMsg {
-        choices: Vec<Choice>,
-    }
-
-    #[derive(serde::Deserialize)]
-    struct Choice {
-        message: Message,
-    }
-
-    #[derive(serde::Deserialize)]
-    struct Message {
-        content: String,
+    let request = [
+        MessageRequest::text("system", system_prompt),
+        MessageRequest::text("user", &history_text),
+    ];
+
+    // [ใช้โมเดลของงาน summarization (ถูกกว่าโมเดลแชท)]
+    let completion = complete_with_fallback(
+        state,
+        Task::Summarization,
+        state.router.route(Task::Summarization),
+        &request,
+    ).await?;
+    METRICS.summaries_generated_total.inc();
+    // [เขียนลง quota_file พร้อม usage ของ embedding ด้านล่าง]
+    if let Some(usage) = &completion.usage {
+        state.quota.record(user_id, usage.prompt_tokens, usage.completion_tokens);
     }
 
-    let parsed: ChoiceMsg = serde_json::from_str(&body)?;
-    let summary = parsed.choices.first().map(|c: &Choice| c.message.content.clone())
-        .unwrap_or("ไม่สามารถสรุปเนื้อหาได้".to_string());
+    let summary = if completion.reply.is_empty() {
+        "ไม่สามารถสรุปเนื้อหาได้".to_string()
+    } else {
+        completion.reply
+    };
 
-    let embedding = create_embedding(&openai_key, &summary).await?;
+    let embedding = create_embedding(state, user_id, &summary).await?;
 
-    store_message_to_qdrant(
-        &qdrant,
-        session_id,
-        "summary",
-        &summary,
-        embedding,
-        Utc::now().timestamp()
-    ).await?;
+    // [ผูก summary กับข้อความสุดท้ายที่สรุป ให้ค้นเจอเฉพาะใน branch ที่มีข้อความนั้น]
+    let summary_message = ChatMessage {
+        parent_id: messages.last().map(|m| m.id.clone()),
+        ..ChatMessage::new(&new_message_id(), session_id, "summary", &summary)
+    };
+    store_message_to_qdrant(&state.qdrant_client, &state.config.qdrant, &summary_message, embedding).await?;
 
     Ok(summary)
 }
@@ -0,0 +1,78 @@
+use axum::extract::Request as AxumRequest;
+use axum::http::{HeaderName, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tracing::Level;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// [ให้ AppError::into_response อ่าน request id ได้ โดยไม่ต้องส่ง id ผ่านทุก handler]
+pub async fn scope_request_id(req: AxumRequest, next: Next) -> Response {
+    let request_id = req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    REQUEST_ID.scope(request_id, next.run(req)).await
+}
+
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok().filter(|id| !id.is_empty())
+}
+
+// -----------------------
+// JSON logs
+// ใช้ RUST_LOG (default: info), span ปิดแล้วจะพ่น time.busy/time.idle ให้ดูว่าแต่ละ stage ใช้เวลาเท่าไร
+// -----------------------
+pub fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(filter)
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
+}
+
+pub fn set_request_id_layer() -> SetRequestIdLayer<MakeRequestUuid> {
+    SetRequestIdLayer::new(HeaderName::from_static(REQUEST_ID_HEADER), MakeRequestUuid)
+}
+
+pub fn propagate_request_id_layer() -> PropagateRequestIdLayer {
+    PropagateRequestIdLayer::new(HeaderName::from_static(REQUEST_ID_HEADER))
+}
+
+type MakeSpan = fn(&Request<axum::body::Body>) -> tracing::Span;
+
+pub fn trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>, MakeSpan> {
+    TraceLayer::new_for_http()
+        .make_span_with(make_request_span as MakeSpan)
+        .on_response(DefaultOnResponse::new().level(Level::INFO))
+}
+
+fn make_request_span(req: &Request<axum::body::Body>) -> tracing::Span {
+    let request_id = req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "http_request",
+        request_id = %request_id,
+        method = %req.method(),
+        uri = %req.uri(),
+    )
+}